@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS password_reset_tokens;
+ALTER TABLE users DROP COLUMN IF EXISTS recovery_email;
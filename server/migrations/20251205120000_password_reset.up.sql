@@ -0,0 +1,11 @@
+-- Add up migration script here
+ALTER TABLE users ADD COLUMN recovery_email TEXT;
+
+-- password_reset_tokens
+CREATE TABLE password_reset_tokens (
+    id          UUID PRIMARY KEY,
+    user_id     UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_hash  TEXT NOT NULL UNIQUE,
+    expires_at  TIMESTAMPTZ NOT NULL,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
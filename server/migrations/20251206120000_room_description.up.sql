@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE rooms ADD COLUMN description TEXT;
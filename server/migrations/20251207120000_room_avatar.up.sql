@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE rooms ADD COLUMN avatar_file_id UUID REFERENCES files(id) ON DELETE SET NULL;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE users DROP COLUMN IF EXISTS avatar_file_id;
+ALTER TABLE files DROP COLUMN IF EXISTS uploader_id;
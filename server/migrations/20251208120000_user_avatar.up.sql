@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE files ADD COLUMN uploader_id UUID REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE users ADD COLUMN avatar_file_id UUID REFERENCES files(id) ON DELETE SET NULL;
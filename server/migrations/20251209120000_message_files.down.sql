@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_user_messages_file_id;
+ALTER TABLE user_messages DROP COLUMN IF EXISTS file_id;
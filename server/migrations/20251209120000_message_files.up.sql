@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE user_messages ADD COLUMN file_id UUID REFERENCES files(id) ON DELETE SET NULL;
+CREATE INDEX idx_user_messages_file_id ON user_messages(file_id) WHERE file_id IS NOT NULL;
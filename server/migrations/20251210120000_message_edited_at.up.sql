@@ -0,0 +1,2 @@
+-- Add up migration script here
+ALTER TABLE user_messages ADD COLUMN edited_at TIMESTAMPTZ;
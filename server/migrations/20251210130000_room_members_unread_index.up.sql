@@ -0,0 +1,2 @@
+-- Add up migration script here
+CREATE INDEX idx_room_members_unread ON room_members(user_id) WHERE unread_count > 0;
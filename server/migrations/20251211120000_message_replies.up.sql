@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE user_messages ADD COLUMN reply_to UUID REFERENCES user_messages(id) ON DELETE SET NULL;
+CREATE INDEX idx_user_messages_reply_to ON user_messages(reply_to) WHERE reply_to IS NOT NULL;
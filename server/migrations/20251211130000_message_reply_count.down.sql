@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE user_messages DROP COLUMN IF EXISTS reply_count;
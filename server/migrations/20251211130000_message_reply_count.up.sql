@@ -0,0 +1,12 @@
+-- Add up migration script here
+ALTER TABLE user_messages ADD COLUMN reply_count INT NOT NULL DEFAULT 0;
+
+UPDATE user_messages m
+SET reply_count = r.count
+FROM (
+    SELECT reply_to, COUNT(*) AS count
+    FROM user_messages
+    WHERE reply_to IS NOT NULL
+    GROUP BY reply_to
+) r
+WHERE m.id = r.reply_to;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS signed_prekeys_one_current;
+ALTER TABLE signed_prekeys DROP COLUMN IF EXISTS is_current;
@@ -0,0 +1,13 @@
+-- Add up migration script here
+ALTER TABLE signed_prekeys ADD COLUMN is_current BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE signed_prekeys s
+SET is_current = TRUE
+FROM (
+    SELECT DISTINCT ON (user_id) id
+    FROM signed_prekeys
+    ORDER BY user_id, created_at DESC, key_id DESC
+) latest
+WHERE s.id = latest.id;
+
+CREATE UNIQUE INDEX signed_prekeys_one_current ON signed_prekeys (user_id) WHERE is_current;
@@ -0,0 +1,8 @@
+-- Add up migration script here
+CREATE TABLE message_reactions (
+    message_id      UUID NOT NULL REFERENCES user_messages(id) ON DELETE CASCADE,
+    user_id         UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    emoji           TEXT NOT NULL,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (message_id, user_id, emoji)
+);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS invitations_pending_expires_at;
+
+ALTER TABLE invitations DROP COLUMN IF EXISTS expires_at;
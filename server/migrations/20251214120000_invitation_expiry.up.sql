@@ -0,0 +1,8 @@
+-- Add up migration script here
+ALTER TABLE invitations ADD COLUMN expires_at TIMESTAMPTZ;
+
+UPDATE invitations SET expires_at = created_at + INTERVAL '7 days';
+
+ALTER TABLE invitations ALTER COLUMN expires_at SET NOT NULL;
+
+CREATE INDEX invitations_pending_expires_at ON invitations (expires_at) WHERE status = 'pending';
@@ -0,0 +1,8 @@
+-- Add up migration script here
+CREATE TABLE room_drafts (
+    user_id         UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    room_id         UUID NOT NULL REFERENCES rooms(id) ON DELETE CASCADE,
+    content         TEXT NOT NULL,
+    updated_at      TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, room_id)
+);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS user_messages_room_message_count ON user_messages;
+DROP FUNCTION IF EXISTS maintain_room_message_count();
+ALTER TABLE rooms DROP COLUMN IF EXISTS message_count;
@@ -0,0 +1,27 @@
+-- Add up migration script here
+ALTER TABLE rooms ADD COLUMN message_count BIGINT NOT NULL DEFAULT 0;
+
+UPDATE rooms r
+SET message_count = m.count
+FROM (
+    SELECT room_id, COUNT(*) AS count
+    FROM user_messages
+    GROUP BY room_id
+) m
+WHERE r.id = m.room_id;
+
+-- Kept by a trigger so every insert path and cascading delete is covered
+CREATE FUNCTION maintain_room_message_count() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        UPDATE rooms SET message_count = message_count + 1 WHERE id = NEW.room_id;
+    ELSE
+        UPDATE rooms SET message_count = message_count - 1 WHERE id = OLD.room_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER user_messages_room_message_count
+AFTER INSERT OR DELETE ON user_messages
+FOR EACH ROW EXECUTE FUNCTION maintain_room_message_count();
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE user_messages ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT false;
+CREATE INDEX idx_user_messages_pinned ON user_messages(room_id) WHERE pinned;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE refresh_tokens ADD COLUMN used_at TIMESTAMPTZ;
+ALTER TABLE refresh_tokens ADD COLUMN replaced_by UUID REFERENCES refresh_tokens(id) ON DELETE SET NULL;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+ALTER TABLE invitations DROP CONSTRAINT IF EXISTS invitations_status_check;
+ALTER TABLE user_messages DROP CONSTRAINT IF EXISTS user_messages_status_check;
+ALTER TABLE user_messages DROP CONSTRAINT IF EXISTS user_messages_message_type_check;
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_role_check;
@@ -0,0 +1,10 @@
+-- Add up migration script here
+-- Keep these lists in step with the enums in src/database/models.rs
+ALTER TABLE users
+    ADD CONSTRAINT users_role_check CHECK (role IN ('admin', 'user'));
+ALTER TABLE user_messages
+    ADD CONSTRAINT user_messages_message_type_check CHECK (message_type IN ('text', 'file', 'system'));
+ALTER TABLE user_messages
+    ADD CONSTRAINT user_messages_status_check CHECK (status IN ('sent', 'edited', 'deleted'));
+ALTER TABLE invitations
+    ADD CONSTRAINT invitations_status_check CHECK (status IN ('pending', 'accepted', 'declined'));
@@ -0,0 +1,9 @@
+-- Add up migration script here
+-- One room per pair of users, keyed by the pair in ascending id order
+CREATE TABLE direct_rooms (
+    user_low  UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    user_high UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    room_id   UUID NOT NULL UNIQUE REFERENCES rooms(id) ON DELETE CASCADE,
+    PRIMARY KEY (user_low, user_high),
+    CHECK (user_low < user_high)
+);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS recovery_email_tokens;
+ALTER TABLE users DROP COLUMN IF EXISTS recovery_email_verified_at;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TABLE rooms ADD COLUMN is_direct BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE rooms SET is_direct = TRUE WHERE id IN (SELECT room_id FROM direct_rooms);
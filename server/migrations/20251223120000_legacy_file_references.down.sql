@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE files DROP COLUMN IF EXISTS legacy_reference;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- Files uploaded before messages linked them by `file_id` are only referenced
+-- from encrypted message content, so orphan cleanup must leave them alone
+ALTER TABLE files ADD COLUMN legacy_reference BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE files ALTER COLUMN legacy_reference SET DEFAULT FALSE;
@@ -1,48 +1,453 @@
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    env::VarError,
+    net::{IpAddr, Ipv4Addr},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex, atomic::AtomicUsize},
+    time::Instant,
+};
 
 use dashmap::DashMap;
+use scrypt::Params;
+use thiserror::Error;
 use tokio::sync::mpsc;
-use tracing::instrument;
+use tracing::{error, instrument};
 use uuid::Uuid;
 
-use crate::{database::db::Db, dtos::ServerResp};
+use crate::{
+    database::db::Db,
+    dtos::ServerResp,
+    errors::error::AppError,
+    utils::{metrics::Metrics, notifier::Notifier},
+};
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub database_url: String,
     pub jwt_secret: String,
+    pub bind_addr: IpAddr,
     pub port: u16,
     pub access_expiry: i64,
     pub refresh_expiry: i64,
+    pub password_reset_enabled: bool,
+    pub password_reset_expiry: i64,
+    pub notifier_webhook_url: Option<String>,
+    pub allowed_origins: Vec<String>,
+    pub file_upload_enabled: bool,
+    pub scrypt_log_n: u8,
+    pub scrypt_r: u32,
+    pub scrypt_p: u32,
+    pub orphan_file_grace_period: i64,
+    pub user_search_limit: i64,
+    pub max_pending_invitations: i64,
+    pub unique_room_names: bool,
+    /// Registering or logging in as this user grants the admin role
+    pub bootstrap_admin_username: Option<String>,
+    /// WebSocket requests a connection may make per `ws_rate_window_secs`
+    pub ws_rate_limit: u32,
+    pub ws_rate_window_secs: u64,
+    /// How long authors may delete their own messages; 0 means no limit.
+    /// Room and server admins can delete regardless.
+    pub message_delete_window_secs: i64,
+    /// How long an invitation stays pending before it expires
+    pub invitation_ttl_secs: i64,
+    /// Sockets are pinged this often and dropped when no pong arrives within
+    /// `ws_pong_timeout_secs`
+    pub ws_ping_interval_secs: u64,
+    pub ws_pong_timeout_secs: u64,
+    /// Longest message content accepted, in characters
+    pub max_message_len: usize,
+    /// Key counts below this tell the client to upload more one-time prekeys
+    pub prekey_low_watermark: i64,
+    /// Longest user or message search query accepted, in characters
+    pub max_search_query_len: usize,
+    /// Members a room can hold; joins and invites into a full room fail
+    pub max_room_members: usize,
+    /// WebSocket connections open at once across all users
+    pub max_connections: usize,
+    /// What a new connection does once `max_connections` are open
+    pub connection_overflow: ConnectionOverflow,
+}
+
+/// How to make room for a socket beyond `max_connections`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionOverflow {
+    /// Close the socket that went longest without sending a request
+    EvictIdle,
+    /// Close the new socket instead
+    Reject,
+}
+
+impl FromStr for ConnectionOverflow {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "evict_idle" => Ok(ConnectionOverflow::EvictIdle),
+            "reject" => Ok(ConnectionOverflow::Reject),
+            _ => Err(()),
+        }
+    }
 }
 
 impl Config {
-    #[instrument]
+    /// Panicking wrapper around `from_env`, for tests and tools
     pub fn init() -> Config {
-        let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-        let jwt_secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-        let access_expiry: i64 = std::env::var("ACCESS_TOKEN_EXPIRY")
-            .expect("ACCESS_TOKEN_EXPIRY must be set")
-            .parse()
-            .expect("ACCESS_TOKEN_EXPIRY must be a valid u64");
-        let refresh_expiry: i64 = std::env::var("REFRESH_TOKEN_EXPIRY")
-            .expect("REFRESH_TOKEN_EXPIRY must be set")
-            .parse()
-            .expect("REFRESH_TOKEN_EXPIRY must be a valid u64");
-
-        Config {
+        Self::from_env().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn from_env() -> Result<Config, ConfigError> {
+        Self::try_from_source(&ConfigSource::load()?)
+    }
+
+    /// Reads every setting before failing, so the error lists all problems
+    #[instrument(skip(source))]
+    pub fn try_from_source(source: &ConfigSource) -> Result<Config, ConfigError> {
+        let mut vars = VarReader::new(source);
+        let database_url: Option<String> = vars.required("DATABASE_URL", "a string");
+        let jwt_secret: Option<String> = vars.required("JWT_SECRET", "a string");
+        let access_expiry: Option<i64> = vars.required("ACCESS_TOKEN_EXPIRY", "a valid u64");
+        let refresh_expiry: Option<i64> = vars.required("REFRESH_TOKEN_EXPIRY", "a valid u64");
+        let bind_addr = vars.optional(
+            "BIND_ADDR",
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            "a valid IP address",
+        );
+        let password_reset_enabled =
+            vars.optional("PASSWORD_RESET_ENABLED", false, "true or false");
+        let password_reset_expiry: i64 = vars.optional("PASSWORD_RESET_EXPIRY", 900, "a valid i64");
+        let notifier_webhook_url = source.var("NOTIFIER_WEBHOOK_URL").ok();
+        // Comma separated; when empty only the server's own origin may open a
+        // WebSocket from a browser
+        let allowed_origins: Vec<String> = source
+            .var("ALLOWED_ORIGINS")
+            .map(|v| {
+                v.split(',')
+                    .map(|o| o.trim().to_string())
+                    .filter(|o| !o.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let file_upload_enabled = vars.optional("FILE_UPLOAD_ENABLED", true, "true or false");
+        let scrypt_log_n: u8 =
+            vars.optional("SCRYPT_LOG_N", Params::RECOMMENDED_LOG_N, "a valid u8");
+        let scrypt_r: u32 = vars.optional("SCRYPT_R", Params::RECOMMENDED_R, "a valid u32");
+        let scrypt_p: u32 = vars.optional("SCRYPT_P", Params::RECOMMENDED_P, "a valid u32");
+        let orphan_file_grace_period: i64 =
+            vars.optional("ORPHAN_FILE_GRACE_PERIOD", 86400, "a valid i64");
+        let user_search_limit: i64 = vars.optional("USER_SEARCH_LIMIT", 20, "a valid i64");
+        let max_pending_invitations: i64 =
+            vars.optional("MAX_PENDING_INVITATIONS", 100, "a valid i64");
+        // Case-insensitive uniqueness of room names per creator
+        let unique_room_names = vars.optional("UNIQUE_ROOM_NAMES", false, "true or false");
+        let bootstrap_admin_username = source.var("BOOTSTRAP_ADMIN_USERNAME").ok();
+        let ws_rate_limit: u32 = vars.optional("WS_RATE_LIMIT", 20, "a valid u32");
+        let ws_rate_window_secs: u64 = vars.optional("WS_RATE_WINDOW_SECS", 10, "a valid u64");
+        let message_delete_window_secs: i64 =
+            vars.optional("MESSAGE_DELETE_WINDOW_SECS", 0, "a valid i64");
+        let invitation_ttl_secs: i64 = vars.optional("INVITATION_TTL_SECS", 604800, "a valid u64");
+        let ws_ping_interval_secs: u64 = vars.optional("WS_PING_INTERVAL_SECS", 30, "a valid u64");
+        let ws_pong_timeout_secs: u64 = vars.optional("WS_PONG_TIMEOUT_SECS", 60, "a valid u64");
+        let max_message_len: usize = vars.optional("MAX_MESSAGE_LEN", 4000, "a valid u64");
+        let prekey_low_watermark: i64 = vars.optional("PREKEY_LOW_WATERMARK", 10, "a valid i64");
+        let max_search_query_len: usize = vars.optional("MAX_SEARCH_QUERY_LEN", 64, "a valid u64");
+        let max_room_members: usize = vars.optional("MAX_ROOM_MEMBERS", 1000, "a valid u64");
+        let max_connections: usize = vars.optional("MAX_CONNECTIONS", 10000, "a valid u64");
+        let connection_overflow = vars.optional(
+            "CONNECTION_OVERFLOW",
+            ConnectionOverflow::EvictIdle,
+            "evict_idle or reject",
+        );
+        if Params::new(scrypt_log_n, scrypt_r, scrypt_p, Params::RECOMMENDED_LEN).is_err() {
+            vars.invalid.push(
+                "SCRYPT_LOG_N, SCRYPT_R and SCRYPT_P must form valid scrypt parameters".to_string(),
+            );
+        }
+
+        if ws_rate_limit == 0 || ws_rate_window_secs == 0 {
+            vars.invalid
+                .push("WS_RATE_LIMIT and WS_RATE_WINDOW_SECS must be positive".to_string());
+        }
+
+        if ws_ping_interval_secs == 0 || ws_pong_timeout_secs == 0 {
+            vars.invalid.push(
+                "WS_PING_INTERVAL_SECS and WS_PONG_TIMEOUT_SECS must be positive".to_string(),
+            );
+        }
+
+        if max_message_len == 0 {
+            vars.invalid
+                .push("MAX_MESSAGE_LEN must be positive".to_string());
+        }
+
+        if max_room_members == 0 {
+            vars.invalid
+                .push("MAX_ROOM_MEMBERS must be positive".to_string());
+        }
+
+        if max_connections == 0 {
+            vars.invalid
+                .push("MAX_CONNECTIONS must be positive".to_string());
+        }
+
+        if password_reset_expiry <= 0 {
+            vars.invalid
+                .push("PASSWORD_RESET_EXPIRY must be positive".to_string());
+        }
+
+        // A negative grace period would put fresh uploads up for cleanup
+        if orphan_file_grace_period <= 0 {
+            vars.invalid
+                .push("ORPHAN_FILE_GRACE_PERIOD must be positive".to_string());
+        }
+
+        if user_search_limit <= 0 || max_pending_invitations <= 0 {
+            vars.invalid
+                .push("USER_SEARCH_LIMIT and MAX_PENDING_INVITATIONS must be positive".to_string());
+        }
+
+        // Zero leaves deletion unlimited
+        if message_delete_window_secs < 0 {
+            vars.invalid
+                .push("MESSAGE_DELETE_WINDOW_SECS must not be negative".to_string());
+        }
+
+        if invitation_ttl_secs <= 0 {
+            vars.invalid
+                .push("INVITATION_TTL_SECS must be positive".to_string());
+        }
+
+        if prekey_low_watermark <= 0 {
+            vars.invalid
+                .push("PREKEY_LOW_WATERMARK must be positive".to_string());
+        }
+
+        let (Some(database_url), Some(jwt_secret), Some(access_expiry), Some(refresh_expiry)) =
+            (database_url, jwt_secret, access_expiry, refresh_expiry)
+        else {
+            return Err(vars.into_error());
+        };
+        if !vars.invalid.is_empty() {
+            return Err(vars.into_error());
+        }
+
+        Ok(Config {
             database_url,
             jwt_secret,
+            bind_addr,
             port: 3000,
             access_expiry,
             refresh_expiry,
+            password_reset_enabled,
+            password_reset_expiry,
+            notifier_webhook_url,
+            allowed_origins,
+            file_upload_enabled,
+            scrypt_log_n,
+            scrypt_r,
+            scrypt_p,
+            orphan_file_grace_period,
+            user_search_limit,
+            max_pending_invitations,
+            unique_room_names,
+            bootstrap_admin_username,
+            ws_rate_limit,
+            ws_rate_window_secs,
+            message_delete_window_secs,
+            invitation_ttl_secs,
+            ws_ping_interval_secs,
+            ws_pong_timeout_secs,
+            max_message_len,
+            prekey_low_watermark,
+            max_search_query_len,
+            max_room_members,
+            max_connections,
+            connection_overflow,
+        })
+    }
+
+    /// Cost parameters for new password hashes. Existing hashes carry their own
+    /// parameters, so changing these doesn't break verification.
+    pub fn scrypt_params(&self) -> Result<Params, AppError> {
+        Params::new(
+            self.scrypt_log_n,
+            self.scrypt_r,
+            self.scrypt_p,
+            Params::RECOMMENDED_LEN,
+        )
+        .map_err(|e| {
+            error!("Invalid scrypt parameters: {:?}", e);
+            AppError::Internal
+        })
+    }
+}
+
+/// What a socket's send task is asked to write
+#[derive(Debug, Clone)]
+pub enum WsOutbound {
+    Event(ServerResp),
+    /// Heartbeat, answered by the client with a pong
+    Ping,
+    /// Sends a close frame and ends the connection
+    Close {
+        code: u16,
+        reason: &'static str,
+    },
+}
+
+/// One live WebSocket of a user, who may have several open at once. The id
+/// tells them apart in logs and lets a closing socket remove only itself.
+#[derive(Debug, Clone)]
+pub struct Connection {
+    pub id: Uuid,
+    pub tx: mpsc::UnboundedSender<WsOutbound>,
+    /// When the client last sent a request, for evicting idle sockets
+    pub last_active: Arc<Mutex<Instant>>,
+}
+
+/// Settings `Config` is built from. Environment variables win over the TOML
+/// file named by `CONFIG_FILE`, which wins over the built-in defaults.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSource {
+    env: HashMap<String, String>,
+    file: HashMap<String, String>,
+}
+
+impl ConfigSource {
+    pub fn load() -> Result<ConfigSource, ConfigError> {
+        let env: HashMap<String, String> = std::env::vars().collect();
+        let path = env.get("CONFIG_FILE").map(PathBuf::from);
+        Self::new(env, path.as_deref())
+    }
+
+    /// File keys are the variable names in lower case, e.g. `jwt_secret`
+    pub fn new(
+        env: HashMap<String, String>,
+        file: Option<&Path>,
+    ) -> Result<ConfigSource, ConfigError> {
+        let file = match file {
+            Some(path) => {
+                let file_error = |reason: String| ConfigError::File {
+                    path: path.display().to_string(),
+                    reason,
+                };
+                let table: toml::Table = std::fs::read_to_string(path)
+                    .map_err(|e| file_error(e.to_string()))?
+                    .parse()
+                    .map_err(|e: toml::de::Error| file_error(e.to_string()))?;
+                table
+                    .into_iter()
+                    .map(|(key, value)| (key.to_uppercase(), toml_value_to_var(value)))
+                    .collect()
+            }
+            None => HashMap::new(),
+        };
+
+        Ok(ConfigSource { env, file })
+    }
+
+    fn var(&self, name: &str) -> Result<String, VarError> {
+        self.env
+            .get(name)
+            .or_else(|| self.file.get(name))
+            .cloned()
+            .ok_or(VarError::NotPresent)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Failed to load config file {path}: {reason}")]
+    File { path: String, reason: String },
+    #[error("{}", describe_invalid_settings(missing, invalid))]
+    Settings {
+        missing: Vec<&'static str>,
+        invalid: Vec<String>,
+    },
+}
+
+fn describe_invalid_settings(missing: &[&str], invalid: &[String]) -> String {
+    let mut lines = Vec::new();
+    if !missing.is_empty() {
+        lines.push(format!("Missing required settings: {}", missing.join(", ")));
+    }
+    lines.extend(invalid.iter().cloned());
+    lines.join("\n")
+}
+
+/// Collects missing and malformed settings instead of stopping at the first
+struct VarReader<'a> {
+    source: &'a ConfigSource,
+    missing: Vec<&'static str>,
+    invalid: Vec<String>,
+}
+
+impl<'a> VarReader<'a> {
+    fn new(source: &'a ConfigSource) -> Self {
+        Self {
+            source,
+            missing: Vec::new(),
+            invalid: Vec::new(),
+        }
+    }
+
+    fn required<T: FromStr>(&mut self, name: &'static str, expected: &str) -> Option<T> {
+        match self.source.var(name) {
+            Ok(value) => self.parse(name, &value, expected),
+            Err(_) => {
+                self.missing.push(name);
+                None
+            }
+        }
+    }
+
+    fn optional<T: FromStr>(&mut self, name: &'static str, default: T, expected: &str) -> T {
+        match self.source.var(name) {
+            Ok(value) => self.parse(name, &value, expected).unwrap_or(default),
+            Err(_) => default,
+        }
+    }
+
+    fn parse<T: FromStr>(&mut self, name: &str, value: &str, expected: &str) -> Option<T> {
+        let parsed = value.parse().ok();
+        if parsed.is_none() {
+            self.invalid.push(format!("{} must be {}", name, expected));
+        }
+        parsed
+    }
+
+    fn into_error(self) -> ConfigError {
+        ConfigError::Settings {
+            missing: self.missing,
+            invalid: self.invalid,
         }
     }
 }
 
+/// Renders a file value the way it would be written as an environment variable
+fn toml_value_to_var(value: toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s,
+        toml::Value::Array(items) => items
+            .into_iter()
+            .map(toml_value_to_var)
+            .collect::<Vec<_>>()
+            .join(","),
+        other => other.to_string(),
+    }
+}
+
 #[derive(Clone)]
 pub struct AppState {
     pub config: Arc<Config>,
     pub db: Arc<Db>,
-    pub channels: Arc<DashMap<Uuid, mpsc::UnboundedSender<ServerResp>>>,
+    /// Every open socket of each connected user
+    pub channels: Arc<DashMap<Uuid, Vec<Connection>>>,
+    /// Sockets across `channels`, so admission doesn't have to count them
+    pub open_connections: Arc<AtomicUsize>,
+    pub notifier: Arc<dyn Notifier>,
+    pub metrics: Arc<Metrics>,
+    /// When each `(room_id, user_id)` last reported typing; never persisted
+    pub typing: Arc<DashMap<(Uuid, Uuid), Instant>>,
 }
@@ -0,0 +1,65 @@
+use async_trait::async_trait;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::database::{db::Db, models::RoomDraft};
+
+#[async_trait]
+pub trait DraftRepository: Send + Sync {
+    /// Overwrites any earlier draft of the user's for the room
+    async fn save_draft(
+        &self,
+        user_id: Uuid,
+        room_id: Uuid,
+        content: &str,
+    ) -> Result<RoomDraft, sqlx::Error>;
+
+    async fn get_draft(
+        &self,
+        user_id: Uuid,
+        room_id: Uuid,
+    ) -> Result<Option<RoomDraft>, sqlx::Error>;
+}
+
+#[async_trait]
+impl DraftRepository for Db {
+    #[instrument(skip(self, content))]
+    async fn save_draft(
+        &self,
+        user_id: Uuid,
+        room_id: Uuid,
+        content: &str,
+    ) -> Result<RoomDraft, sqlx::Error> {
+        sqlx::query_as::<_, RoomDraft>(
+            r#"
+            INSERT INTO room_drafts (user_id, room_id, content, updated_at)
+            VALUES ($1, $2, $3, NOW())
+            ON CONFLICT (user_id, room_id)
+            DO UPDATE SET content = EXCLUDED.content, updated_at = EXCLUDED.updated_at
+            RETURNING *
+            "#,
+        )
+        .bind(user_id)
+        .bind(room_id)
+        .bind(content)
+        .fetch_one(self.pool())
+        .await
+    }
+
+    #[instrument(skip(self))]
+    async fn get_draft(
+        &self,
+        user_id: Uuid,
+        room_id: Uuid,
+    ) -> Result<Option<RoomDraft>, sqlx::Error> {
+        sqlx::query_as::<_, RoomDraft>(
+            r#"
+            SELECT * FROM room_drafts WHERE user_id = $1 AND room_id = $2
+            "#,
+        )
+        .bind(user_id)
+        .bind(room_id)
+        .fetch_optional(self.pool())
+        .await
+    }
+}
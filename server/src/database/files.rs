@@ -1,10 +1,20 @@
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use tracing::instrument;
 use uuid::Uuid;
 
 use crate::database::{db::Db, models::FileRecord};
 
+/// An upload that hasn't been stored yet
+#[derive(Debug)]
+pub struct NewFile {
+    pub encrypted_data: Vec<u8>,
+    pub encrypted_metadata: Option<Vec<u8>>,
+    pub size_in_bytes: i64,
+    pub file_hash: String,
+    pub uploader_id: Uuid,
+}
+
 #[async_trait]
 pub trait FileRepository: Send + Sync {
     async fn insert_file(
@@ -13,11 +23,15 @@ pub trait FileRepository: Send + Sync {
         encrypted_metadata: Option<Vec<u8>>,
         size_in_bytes: i64,
         file_hash: String,
+        uploader_id: Uuid,
     ) -> Result<FileRecord, sqlx::Error>;
 
     async fn get_file(&self, file_id: Uuid) -> Result<Option<FileRecord>, sqlx::Error>;
 
     async fn delete_file(&self, file_id: Uuid) -> Result<Option<FileRecord>, sqlx::Error>;
+
+    async fn delete_orphan_files(&self, uploaded_before: DateTime<Utc>)
+    -> Result<u64, sqlx::Error>;
 }
 
 #[async_trait]
@@ -29,11 +43,12 @@ impl FileRepository for Db {
         encrypted_metadata: Option<Vec<u8>>,
         size_in_bytes: i64,
         file_hash: String,
+        uploader_id: Uuid,
     ) -> Result<FileRecord, sqlx::Error> {
         sqlx::query_as::<_, FileRecord>(
             r#"
-            INSERT INTO files (id, encrypted_data, encrypted_metadata, size_in_bytes, file_hash, uploaded_at)
-            VALUES ($1, $2, $3, $4, $5, $6)
+            INSERT INTO files (id, encrypted_data, encrypted_metadata, size_in_bytes, file_hash, uploaded_at, uploader_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
             RETURNING *
             "#,
         )
@@ -43,6 +58,7 @@ impl FileRepository for Db {
         .bind(size_in_bytes)
         .bind(file_hash)
         .bind(Utc::now())
+        .bind(uploader_id)
         .fetch_one(self.pool())
         .await
     }
@@ -71,4 +87,28 @@ impl FileRepository for Db {
         .fetch_optional(self.pool())
         .await
     }
+
+    /// Deletes files nothing points at. Avatars count as references too.
+    /// Legacy uploads are skipped, as their messages never set `file_id`.
+    #[instrument(skip(self))]
+    async fn delete_orphan_files(
+        &self,
+        uploaded_before: DateTime<Utc>,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM files f
+            WHERE f.uploaded_at < $1
+            AND NOT f.legacy_reference
+            AND NOT EXISTS (SELECT 1 FROM user_messages m WHERE m.file_id = f.id)
+            AND NOT EXISTS (SELECT 1 FROM users u WHERE u.avatar_file_id = f.id)
+            AND NOT EXISTS (SELECT 1 FROM rooms r WHERE r.avatar_file_id = f.id)
+            "#,
+        )
+        .bind(uploaded_before)
+        .execute(self.pool())
+        .await?;
+
+        Ok(result.rows_affected())
+    }
 }
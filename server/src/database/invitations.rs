@@ -5,11 +5,12 @@ use uuid::Uuid;
 
 use crate::database::{
     db::Db,
-    models::{Invitation, InvitationStatus},
+    models::{Invitation, InvitationStatus, RoomMember},
 };
 
 #[async_trait]
 pub trait InvitationRepository: Send + Sync {
+    #[allow(clippy::too_many_arguments)]
     async fn create_invitation(
         &self,
         room_id: Uuid,
@@ -18,6 +19,7 @@ pub trait InvitationRepository: Send + Sync {
         invitee_username: String,
         inviter_id: Uuid,
         inviter_username: String,
+        expires_at: DateTime<Utc>,
     ) -> Result<Option<Invitation>, sqlx::Error>;
 
     async fn update_invitation_status(
@@ -41,6 +43,13 @@ pub trait InvitationRepository: Send + Sync {
         user_id: Uuid,
     ) -> Result<Vec<Invitation>, sqlx::Error>;
 
+    async fn count_pending_invitations_for_user(&self, user_id: Uuid) -> Result<i64, sqlx::Error>;
+
+    async fn get_pending_invitations_for_room(
+        &self,
+        room_id: Uuid,
+    ) -> Result<Vec<Invitation>, sqlx::Error>;
+
     async fn consume_invitations_and_join_room(
         &self,
         room_id: Uuid,
@@ -48,11 +57,15 @@ pub trait InvitationRepository: Send + Sync {
         user_id: Uuid,
         username: String,
         joined_at: DateTime<Utc>,
-    ) -> Result<(), sqlx::Error>;
+    ) -> Result<Vec<RoomMember>, sqlx::Error>;
+
+    /// Deletes pending invitations past their expiry, returning how many
+    async fn cleanup_expired_invitations(&self) -> Result<u64, sqlx::Error>;
 }
 
 #[async_trait]
 impl InvitationRepository for Db {
+    #[allow(clippy::too_many_arguments)]
     #[instrument(skip(self))]
     async fn create_invitation(
         &self,
@@ -62,11 +75,29 @@ impl InvitationRepository for Db {
         invitee_username: String,
         inviter_id: Uuid,
         inviter_username: String,
+        expires_at: DateTime<Utc>,
     ) -> Result<Option<Invitation>, sqlx::Error> {
-        sqlx::query_as::<_, Invitation>(
+        let mut tx = self.pool().begin().await?;
+
+        // An expired invitation the cleanup task hasn't reached yet shouldn't
+        // block inviting the same user again
+        sqlx::query(
+            r#"
+            DELETE FROM invitations
+            WHERE room_id = $1 AND invitee_id = $2 AND inviter_id = $3
+            AND status = 'pending' AND expires_at <= NOW()
+            "#,
+        )
+        .bind(room_id)
+        .bind(invitee_id)
+        .bind(inviter_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let invitation = sqlx::query_as::<_, Invitation>(
             r#"
-            INSERT INTO invitations (id, room_id, room_name, invitee_id, invitee_username, inviter_id, inviter_username, status, created_at)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            INSERT INTO invitations (id, room_id, room_name, invitee_id, invitee_username, inviter_id, inviter_username, status, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
             ON CONFLICT (room_id, invitee_id, inviter_id) WHERE status = 'pending'
             DO NOTHING
             RETURNING *
@@ -81,8 +112,12 @@ impl InvitationRepository for Db {
         .bind(inviter_username)
         .bind(InvitationStatus::Pending)
         .bind(Utc::now())
-        .fetch_optional(self.pool())
-        .await
+        .bind(expires_at)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(invitation)
     }
 
     #[instrument(skip(self))]
@@ -143,11 +178,43 @@ impl InvitationRepository for Db {
         sqlx::query_as::<_, Invitation>(
             r#"
             SELECT * FROM invitations
-            WHERE invitee_id = $1 AND status = $2
+            WHERE invitee_id = $1 AND status = $2 AND expires_at > NOW()
+            "#,
+        )
+        .bind(user_id)
+        .bind(InvitationStatus::Pending)
+        .fetch_all(self.pool())
+        .await
+    }
+
+    #[instrument(skip(self))]
+    async fn count_pending_invitations_for_user(&self, user_id: Uuid) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar::<_, i64>(
+            r#"
+            SELECT COUNT(*) FROM invitations
+            WHERE invitee_id = $1 AND status = $2 AND expires_at > NOW()
             "#,
         )
         .bind(user_id)
         .bind(InvitationStatus::Pending)
+        .fetch_one(self.pool())
+        .await
+    }
+
+    #[instrument(skip(self))]
+    async fn get_pending_invitations_for_room(
+        &self,
+        room_id: Uuid,
+    ) -> Result<Vec<Invitation>, sqlx::Error> {
+        sqlx::query_as::<_, Invitation>(
+            r#"
+            SELECT * FROM invitations
+            WHERE room_id = $1 AND status = $2 AND expires_at > NOW()
+            ORDER BY created_at
+            "#,
+        )
+        .bind(room_id)
+        .bind(InvitationStatus::Pending)
         .fetch_all(self.pool())
         .await
     }
@@ -160,14 +227,20 @@ impl InvitationRepository for Db {
         user_id: Uuid,
         username: String,
         joined_at: DateTime<Utc>,
-    ) -> Result<(), sqlx::Error> {
+    ) -> Result<Vec<RoomMember>, sqlx::Error> {
         let mut tx = self.pool().begin().await?;
 
+        // Serialize joins per room so each joiner sees everyone who joined before
+        sqlx::query(r#"SELECT id FROM rooms WHERE id = $1 FOR UPDATE"#)
+            .bind(room_id)
+            .execute(&mut *tx)
+            .await?;
+
         sqlx::query(
             r#"
             UPDATE invitations
             SET status = $3
-            WHERE room_id = $1 AND invitee_id = $2 AND status = 'pending'
+            WHERE room_id = $1 AND invitee_id = $2 AND status = 'pending' AND expires_at > NOW()
             "#,
         )
         .bind(room_id)
@@ -185,29 +258,52 @@ impl InvitationRepository for Db {
         .fetch_optional(&mut *tx)
         .await?;
 
-        if is_active.is_some() {
-            tx.commit().await?;
-            return Ok(());
+        if is_active.is_none() {
+            sqlx::query(
+                r#"
+                INSERT INTO room_members (id, room_id, room_name, user_id, username, joined_at, last_read_at, unread_count)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                "#,
+            )
+            .bind(Uuid::new_v4())
+            .bind(room_id)
+            .bind(room_name)
+            .bind(user_id)
+            .bind(username)
+            .bind(joined_at)
+            .bind(Utc::now())
+            .bind(0)
+            .execute(&mut *tx)
+            .await?;
         }
 
-        sqlx::query(
+        let members = sqlx::query_as::<_, RoomMember>(
             r#"
-            INSERT INTO room_members (id, room_id, room_name, user_id, username, joined_at, last_read_at, unread_count)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            SELECT rm.*, u.avatar_file_id
+            FROM room_members rm
+            LEFT JOIN users u ON u.id = rm.user_id
+            WHERE rm.room_id = $1 AND rm.left_at IS NULL
             "#,
         )
-        .bind(Uuid::new_v4())
         .bind(room_id)
-        .bind(room_name)
-        .bind(user_id)
-        .bind(username)
-        .bind(joined_at)
-        .bind(Utc::now())
-        .bind(0)
-        .execute(&mut *tx)
+        .fetch_all(&mut *tx)
         .await?;
 
         tx.commit().await?;
-        Ok(())
+        Ok(members)
+    }
+
+    #[instrument(skip(self))]
+    async fn cleanup_expired_invitations(&self) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM invitations
+            WHERE status = $1 AND expires_at <= NOW()
+            "#,
+        )
+        .bind(InvitationStatus::Pending)
+        .execute(self.pool())
+        .await?;
+        Ok(result.rows_affected())
     }
 }
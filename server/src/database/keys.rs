@@ -23,11 +23,12 @@ pub trait KeyRepository: Send + Sync {
         signature: String,
     ) -> Result<SignedPreKey, sqlx::Error>;
 
+    /// Replaces the user's one-time prekeys and returns how many were stored
     async fn upload_one_time_prekeys(
         &self,
         user_id: Uuid,
         keys: Vec<(i32, String)>,
-    ) -> Result<(), sqlx::Error>;
+    ) -> Result<u64, sqlx::Error>;
 
     async fn get_identity_key(&self, user_id: Uuid) -> Result<Option<IdentityKey>, sqlx::Error>;
 
@@ -76,14 +77,29 @@ impl KeyRepository for Db {
         public_key: String,
         signature: String,
     ) -> Result<SignedPreKey, sqlx::Error> {
-        sqlx::query_as::<_, SignedPreKey>(
+        let mut tx = self.pool().begin().await?;
+        // Serializes uploads per user so two can't both end up current
+        sqlx::query("SELECT 1 FROM users WHERE id = $1 FOR UPDATE")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            "UPDATE signed_prekeys SET is_current = FALSE WHERE user_id = $1 AND is_current",
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+        let signed_prekey = sqlx::query_as::<_, SignedPreKey>(
             r#"
-            INSERT INTO signed_prekeys (id, user_id, key_id, public_key, signature)
-            VALUES ($1, $2, $3, $4, $5)
+            INSERT INTO signed_prekeys (id, user_id, key_id, public_key, signature, is_current)
+            VALUES ($1, $2, $3, $4, $5, TRUE)
             ON CONFLICT (user_id, key_id) DO UPDATE
             SET public_key = EXCLUDED.public_key,
                 signature = EXCLUDED.signature,
-                created_at = NOW()
+                created_at = NOW(),
+                is_current = TRUE
             RETURNING *
             "#,
         )
@@ -92,8 +108,12 @@ impl KeyRepository for Db {
         .bind(key_id)
         .bind(public_key)
         .bind(signature)
-        .fetch_one(self.pool())
-        .await
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(signed_prekey)
     }
 
     #[instrument(skip(self, keys))]
@@ -101,7 +121,7 @@ impl KeyRepository for Db {
         &self,
         user_id: Uuid,
         keys: Vec<(i32, String)>,
-    ) -> Result<(), sqlx::Error> {
+    ) -> Result<u64, sqlx::Error> {
         let mut tx = self.pool().begin().await?;
         // First, clear existing keys to prevent serving stale keys that the client has lost (e.g. after a reinstall/storage wipe)
         sqlx::query("DELETE FROM one_time_prekeys WHERE user_id = $1")
@@ -111,7 +131,7 @@ impl KeyRepository for Db {
 
         let (key_ids, public_keys): (Vec<i32>, Vec<String>) = keys.into_iter().unzip();
 
-        sqlx::query(
+        let inserted = sqlx::query(
             r#"
             INSERT INTO one_time_prekeys (user_id, key_id, public_key)
             SELECT $1, * FROM UNNEST($2::int[], $3::text[])
@@ -122,11 +142,12 @@ impl KeyRepository for Db {
         .bind(&key_ids)
         .bind(&public_keys)
         .execute(&mut *tx)
-        .await?;
+        .await?
+        .rows_affected();
 
         tx.commit().await?;
 
-        Ok(())
+        Ok(inserted)
     }
 
     #[instrument(skip(self))]
@@ -140,7 +161,7 @@ impl KeyRepository for Db {
     #[instrument(skip(self))]
     async fn get_signed_prekey(&self, user_id: Uuid) -> Result<Option<SignedPreKey>, sqlx::Error> {
         sqlx::query_as::<_, SignedPreKey>(
-            "SELECT * FROM signed_prekeys WHERE user_id = $1 ORDER BY created_at DESC LIMIT 1",
+            "SELECT * FROM signed_prekeys WHERE user_id = $1 AND is_current",
         )
         .bind(user_id)
         .fetch_optional(self.pool())
@@ -152,6 +173,8 @@ impl KeyRepository for Db {
         &self,
         user_id: Uuid,
     ) -> Result<Option<OneTimePreKey>, sqlx::Error> {
+        // One statement, so the pick and the delete can't be split across
+        // connections; SKIP LOCKED hands concurrent callers different keys
         sqlx::query_as::<_, OneTimePreKey>(
             r#"
             DELETE FROM one_time_prekeys
@@ -1,8 +1,11 @@
 pub mod db;
+pub mod drafts;
 pub mod files;
 pub mod invitations;
 pub mod keys;
 pub mod models;
+pub mod password_resets;
+pub mod reactions;
 pub mod refresh_token;
 pub mod room_members;
 pub mod rooms;
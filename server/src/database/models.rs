@@ -10,10 +10,13 @@ pub enum UserRole {
     User,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy, sqlx::Type, PartialEq, Eq)]
+/// Stored by name, like the other enums here. Messages sent without a type
+/// are `Text`.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, sqlx::Type, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 #[sqlx(type_name = "text", rename_all = "snake_case")]
 pub enum MessageType {
+    #[default]
     Text,
     File,
     System,
@@ -44,6 +47,9 @@ pub struct User {
     pub password_hash: String,
     pub role: UserRole,
     pub created_at: DateTime<Utc>,
+    pub recovery_email: Option<String>,
+    pub avatar_file_id: Option<Uuid>,
+    pub recovery_email_verified_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
@@ -53,6 +59,29 @@ pub struct RefreshToken {
     pub token_hash: String,
     pub expires_at: DateTime<Utc>,
     pub created_at: DateTime<Utc>,
+    /// Set once the token has been rotated; it must never be presented again
+    pub used_at: Option<DateTime<Utc>>,
+    /// The token issued in its place
+    pub replaced_by: Option<Uuid>,
+}
+
+/// A refresh token as shown for session management, without its hash
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct RefreshTokenSummary {
+    pub id: Uuid,
+    /// Leading characters of the stored hash, enough to tell sessions apart
+    pub fingerprint: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PasswordResetToken {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
@@ -64,6 +93,12 @@ pub struct Room {
     pub admin_id: Uuid,
     pub admin_username: String,
     pub created_at: DateTime<Utc>,
+    pub description: Option<String>,
+    pub avatar_file_id: Option<Uuid>,
+    /// Every message ever stored in the room, soft-deleted ones included
+    pub message_count: i64,
+    /// Created by `StartDirectMessage`; membership and naming are fixed
+    pub is_direct: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
@@ -79,6 +114,9 @@ pub struct RoomMember {
     pub is_visible: bool,
     pub last_read_at: DateTime<Utc>,
     pub unread_count: i32,
+    /// Only populated by queries that join `users`
+    #[sqlx(default)]
+    pub avatar_file_id: Option<Uuid>,
 }
 
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
@@ -92,6 +130,38 @@ pub struct UserMessage {
     pub message_type: MessageType,
     pub status: MessageStatus,
     pub created_at: DateTime<Utc>,
+    pub file_id: Option<Uuid>,
+    pub edited_at: Option<DateTime<Utc>>,
+    /// The message this one replies to
+    pub reply_to: Option<Uuid>,
+    /// Direct replies, kept up to date by `insert_message`
+    pub reply_count: i32,
+    pub pinned: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct MessageReaction {
+    pub message_id: Uuid,
+    pub user_id: Uuid,
+    pub emoji: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A member's unsent message in a room, stored as the client sent it
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct RoomDraft {
+    pub user_id: Uuid,
+    pub room_id: Uuid,
+    pub content: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// How many members reacted to a message with one emoji
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ReactionCount {
+    pub message_id: Uuid,
+    pub emoji: String,
+    pub count: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
@@ -102,6 +172,7 @@ pub struct FileRecord {
     pub size_in_bytes: i64,
     pub file_hash: String,
     pub uploaded_at: DateTime<Utc>,
+    pub uploader_id: Option<Uuid>,
 }
 
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
@@ -115,6 +186,14 @@ pub struct Invitation {
     pub inviter_username: String,
     pub status: InvitationStatus,
     pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Invitation {
+    /// Still waiting on the invitee; expired invitations no longer count
+    pub fn is_pending(&self) -> bool {
+        self.status == InvitationStatus::Pending && self.expires_at > Utc::now()
+    }
 }
 
 impl std::fmt::Display for UserRole {
@@ -130,16 +209,6 @@ impl std::fmt::Display for UserRole {
     }
 }
 
-impl From<String> for UserRole {
-    fn from(value: String) -> Self {
-        match value.as_str() {
-            "admin" => UserRole::Admin,
-            "user" => UserRole::User,
-            _ => UserRole::User,
-        }
-    }
-}
-
 impl std::fmt::Display for MessageType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -154,17 +223,6 @@ impl std::fmt::Display for MessageType {
     }
 }
 
-impl From<String> for MessageType {
-    fn from(value: String) -> Self {
-        match value.as_str() {
-            "text" => MessageType::Text,
-            "file" => MessageType::File,
-            "system" => MessageType::System,
-            _ => MessageType::Text,
-        }
-    }
-}
-
 impl std::fmt::Display for MessageStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -175,17 +233,6 @@ impl std::fmt::Display for MessageStatus {
     }
 }
 
-impl From<String> for MessageStatus {
-    fn from(s: String) -> Self {
-        match s.as_str() {
-            "sent" => MessageStatus::Sent,
-            "edited" => MessageStatus::Edited,
-            "deleted" => MessageStatus::Deleted,
-            _ => MessageStatus::Sent,
-        }
-    }
-}
-
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
 pub struct IdentityKey {
     pub user_id: Uuid,
@@ -202,6 +249,8 @@ pub struct SignedPreKey {
     pub public_key: String,
     pub signature: String,
     pub created_at: DateTime<Utc>,
+    /// The one key handed out in bundles; set on the latest upload
+    pub is_current: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
@@ -225,14 +274,3 @@ impl std::fmt::Display for InvitationStatus {
         )
     }
 }
-
-impl From<String> for InvitationStatus {
-    fn from(value: String) -> Self {
-        match value.as_str() {
-            "pending" => InvitationStatus::Pending,
-            "accepted" => InvitationStatus::Accepted,
-            "declined" => InvitationStatus::Declined,
-            _ => InvitationStatus::Declined,
-        }
-    }
-}
@@ -0,0 +1,104 @@
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::database::{
+    db::Db,
+    models::{PasswordResetToken, User},
+};
+
+#[async_trait]
+pub trait PasswordResetRepository: Send + Sync {
+    async fn insert_password_reset_token(
+        &self,
+        user_id: Uuid,
+        token_hash: &str,
+        expires_in: Duration,
+    ) -> Result<PasswordResetToken, sqlx::Error>;
+
+    async fn consume_reset_token_and_update_password(
+        &self,
+        token_hash: &str,
+        password_hash: &str,
+    ) -> Result<Option<User>, sqlx::Error>;
+}
+
+#[async_trait]
+impl PasswordResetRepository for Db {
+    #[instrument(skip(self, token_hash))]
+    async fn insert_password_reset_token(
+        &self,
+        user_id: Uuid,
+        token_hash: &str,
+        expires_in: Duration,
+    ) -> Result<PasswordResetToken, sqlx::Error> {
+        let created_at = Utc::now();
+        let expires_at = created_at + expires_in;
+
+        sqlx::query_as::<_, PasswordResetToken>(
+            r#"
+            INSERT INTO password_reset_tokens (id, user_id, token_hash, expires_at, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(token_hash)
+        .bind(expires_at)
+        .bind(created_at)
+        .fetch_one(self.pool())
+        .await
+    }
+
+    #[instrument(skip(self, token_hash, password_hash))]
+    async fn consume_reset_token_and_update_password(
+        &self,
+        token_hash: &str,
+        password_hash: &str,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let mut tx = self.pool().begin().await?;
+
+        let token = sqlx::query_as::<_, PasswordResetToken>(
+            r#"
+            DELETE FROM password_reset_tokens
+            WHERE token_hash = $1
+            RETURNING *
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let token = match token {
+            Some(token) if token.expires_at > Utc::now() => token,
+            _ => {
+                tx.commit().await?;
+                return Ok(None);
+            }
+        };
+
+        let user = sqlx::query_as::<_, User>(
+            r#"UPDATE users SET password_hash = $1 WHERE id = $2 RETURNING *"#,
+        )
+        .bind(password_hash)
+        .bind(token.user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        // Any other outstanding reset tokens and sessions for the user are now stale
+        sqlx::query(r#"DELETE FROM password_reset_tokens WHERE user_id = $1"#)
+            .bind(token.user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(r#"DELETE FROM refresh_tokens WHERE user_id = $1"#)
+            .bind(token.user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(user)
+    }
+}
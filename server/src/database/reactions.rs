@@ -0,0 +1,99 @@
+use async_trait::async_trait;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::database::{
+    db::Db,
+    models::{MessageReaction, ReactionCount},
+};
+
+#[async_trait]
+pub trait ReactionRepository: Send + Sync {
+    /// `None` if the user already reacted to the message with this emoji
+    async fn add_reaction(
+        &self,
+        message_id: Uuid,
+        user_id: Uuid,
+        emoji: &str,
+    ) -> Result<Option<MessageReaction>, sqlx::Error>;
+
+    /// `None` if there was no such reaction to remove
+    async fn remove_reaction(
+        &self,
+        message_id: Uuid,
+        user_id: Uuid,
+        emoji: &str,
+    ) -> Result<Option<MessageReaction>, sqlx::Error>;
+
+    /// Per-emoji counts for each of `message_ids` that has any reactions
+    async fn get_reaction_counts(
+        &self,
+        message_ids: &[Uuid],
+    ) -> Result<Vec<ReactionCount>, sqlx::Error>;
+}
+
+#[async_trait]
+impl ReactionRepository for Db {
+    #[instrument(skip(self))]
+    async fn add_reaction(
+        &self,
+        message_id: Uuid,
+        user_id: Uuid,
+        emoji: &str,
+    ) -> Result<Option<MessageReaction>, sqlx::Error> {
+        sqlx::query_as::<_, MessageReaction>(
+            r#"
+            INSERT INTO message_reactions (message_id, user_id, emoji)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (message_id, user_id, emoji) DO NOTHING
+            RETURNING *
+            "#,
+        )
+        .bind(message_id)
+        .bind(user_id)
+        .bind(emoji)
+        .fetch_optional(self.pool())
+        .await
+    }
+
+    #[instrument(skip(self))]
+    async fn remove_reaction(
+        &self,
+        message_id: Uuid,
+        user_id: Uuid,
+        emoji: &str,
+    ) -> Result<Option<MessageReaction>, sqlx::Error> {
+        sqlx::query_as::<_, MessageReaction>(
+            r#"
+            DELETE FROM message_reactions
+            WHERE message_id = $1 AND user_id = $2 AND emoji = $3
+            RETURNING *
+            "#,
+        )
+        .bind(message_id)
+        .bind(user_id)
+        .bind(emoji)
+        .fetch_optional(self.pool())
+        .await
+    }
+
+    #[instrument(skip(self, message_ids))]
+    async fn get_reaction_counts(
+        &self,
+        message_ids: &[Uuid],
+    ) -> Result<Vec<ReactionCount>, sqlx::Error> {
+        // Ordered by first use so emoji keep their place as counts change
+        sqlx::query_as::<_, ReactionCount>(
+            r#"
+            SELECT message_id, emoji, COUNT(*) AS count
+            FROM message_reactions
+            WHERE message_id = ANY($1)
+            GROUP BY message_id, emoji
+            ORDER BY message_id, MIN(created_at)
+            "#,
+        )
+        .bind(message_ids)
+        .fetch_all(self.pool())
+        .await
+    }
+}
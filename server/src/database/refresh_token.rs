@@ -3,7 +3,10 @@ use chrono::{Duration, Utc};
 use tracing::instrument;
 use uuid::Uuid;
 
-use crate::database::{db::Db, models::RefreshToken};
+use crate::database::{
+    db::Db,
+    models::{RefreshToken, RefreshTokenSummary},
+};
 
 #[async_trait]
 pub trait RefreshTokenRepository: Send + Sync {
@@ -18,15 +21,32 @@ pub trait RefreshTokenRepository: Send + Sync {
         expires_in: Duration,
     ) -> Result<RefreshToken, sqlx::Error>;
 
+    /// Only tokens that haven't been rotated yet
     async fn delete_refresh_token_by_hash(
         &self,
         token_hash: &str,
     ) -> Result<Option<RefreshToken>, sqlx::Error>;
 
-    // async fn delete_tokens_for_user(
-    //     &self,
-    //     user_id: Uuid,
-    // ) -> Result<Option<RefreshToken>, sqlx::Error>;
+    /// Retires a rotated token instead of deleting it, so a replay can be
+    /// recognized. `None` if it was already used.
+    async fn mark_refresh_token_used(
+        &self,
+        token_hash: &str,
+        replaced_by: Uuid,
+    ) -> Result<Option<RefreshToken>, sqlx::Error>;
+
+    /// Unexpired tokens, newest first
+    async fn get_refresh_tokens_for_user(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<RefreshTokenSummary>, sqlx::Error>;
+
+    /// Ends every session of the user, returning how many tokens were dropped
+    async fn revoke_tokens_for_user(&self, user_id: Uuid) -> Result<u64, sqlx::Error>;
+
+    /// Deletes tokens that were rotated or expired more than `retention` ago,
+    /// returning how many. Rotated ones are kept that long for reuse detection.
+    async fn purge_stale_refresh_tokens(&self, retention: Duration) -> Result<u64, sqlx::Error>;
 }
 
 #[async_trait]
@@ -83,7 +103,7 @@ impl RefreshTokenRepository for Db {
         sqlx::query_as::<_, RefreshToken>(
             r#"
             DELETE FROM refresh_tokens
-            WHERE token_hash = $1
+            WHERE token_hash = $1 AND used_at IS NULL
             RETURNING *
             "#,
         )
@@ -92,20 +112,74 @@ impl RefreshTokenRepository for Db {
         .await
     }
 
-    // async fn delete_tokens_for_user(
-    //     &self,
-    //     user_id: Uuid,
-    // ) -> Result<Option<RefreshToken>, sqlx::Error> {
-    //     sqlx::query_as!(
-    //         RefreshToken,
-    //         r#"
-    //         DELETE FROM refresh_tokens
-    //         WHERE user_id = $1
-    //         RETURNING *
-    //         "#,
-    //         user_id,
-    //     )
-    //     .fetch_optional(self.pool())
-    //     .await
-    // }
+    #[instrument(skip(self))]
+    async fn mark_refresh_token_used(
+        &self,
+        token_hash: &str,
+        replaced_by: Uuid,
+    ) -> Result<Option<RefreshToken>, sqlx::Error> {
+        sqlx::query_as::<_, RefreshToken>(
+            r#"
+            UPDATE refresh_tokens
+            SET used_at = $2, replaced_by = $3
+            WHERE token_hash = $1 AND used_at IS NULL
+            RETURNING *
+            "#,
+        )
+        .bind(token_hash)
+        .bind(Utc::now())
+        .bind(replaced_by)
+        .fetch_optional(self.pool())
+        .await
+    }
+
+    #[instrument(skip(self))]
+    async fn get_refresh_tokens_for_user(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<RefreshTokenSummary>, sqlx::Error> {
+        sqlx::query_as::<_, RefreshTokenSummary>(
+            r#"
+            SELECT id, LEFT(token_hash, 8) AS fingerprint, expires_at, created_at
+            FROM refresh_tokens
+            WHERE user_id = $1 AND expires_at > $2 AND used_at IS NULL
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .bind(Utc::now())
+        .fetch_all(self.pool())
+        .await
+    }
+
+    #[instrument(skip(self))]
+    async fn revoke_tokens_for_user(&self, user_id: Uuid) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM refresh_tokens
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .execute(self.pool())
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    #[instrument(skip(self))]
+    async fn purge_stale_refresh_tokens(&self, retention: Duration) -> Result<u64, sqlx::Error> {
+        let cutoff = Utc::now() - retention;
+        let result = sqlx::query(
+            r#"
+            DELETE FROM refresh_tokens
+            WHERE used_at < $1 OR expires_at < $1
+            "#,
+        )
+        .bind(cutoff)
+        .execute(self.pool())
+        .await?;
+
+        Ok(result.rows_affected())
+    }
 }
@@ -1,11 +1,11 @@
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use tracing::instrument;
 use uuid::Uuid;
 
 use crate::database::{
     db::Db,
-    models::{RoomMember, UserMessage},
+    models::{Room, RoomMember, UserMessage},
 };
 use sqlx::{FromRow, Row};
 
@@ -27,6 +27,19 @@ pub trait RoomMemberRepository: Send + Sync {
 
     async fn get_members(&self, room_id: Uuid) -> Result<Vec<RoomMember>, sqlx::Error>;
 
+    /// `get_members` without the rows, for picking broadcast targets
+    async fn get_member_ids(&self, room_id: Uuid) -> Result<Vec<Uuid>, sqlx::Error>;
+
+    /// One page of current members, in the order they joined
+    async fn get_members_paginated(
+        &self,
+        room_id: Uuid,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<RoomMember>, sqlx::Error>;
+
+    async fn count_members(&self, room_id: Uuid) -> Result<i64, sqlx::Error>;
+
     async fn is_member(&self, room_id: Uuid, user_id: Uuid) -> Result<bool, sqlx::Error>;
 
     async fn is_admin(&self, room_id: Uuid, user_id: Uuid) -> Result<bool, sqlx::Error>;
@@ -34,16 +47,30 @@ pub trait RoomMemberRepository: Send + Sync {
     async fn get_rooms_info_for_user(
         &self,
         user_id: Uuid,
-    ) -> Result<Vec<(RoomMember, Option<UserMessage>)>, sqlx::Error>;
+    ) -> Result<Vec<(RoomMember, Option<Room>, Option<UserMessage>)>, sqlx::Error>;
+
+    /// The memberships `get_rooms_info_for_user` reports on, without the joins
+    async fn get_rooms_summary_for_user(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<RoomMember>, sqlx::Error>;
+
+    /// Current memberships with at least one unread message
+    async fn get_rooms_with_unread(&self, user_id: Uuid) -> Result<Vec<RoomMember>, sqlx::Error>;
+
+    /// Every room the user is in right now, oldest membership first
+    async fn get_current_memberships(&self, user_id: Uuid) -> Result<Vec<RoomMember>, sqlx::Error>;
 
     async fn increment_unread_count(&self, room_id: Uuid, user_id: Uuid)
     -> Result<(), sqlx::Error>;
 
+    /// Returns the `last_read_at` the member had before the reset, and the
+    /// one as stored now
     async fn reset_last_read_and_count(
         &self,
         room_id: Uuid,
         user_id: Uuid,
-    ) -> Result<(), sqlx::Error>;
+    ) -> Result<Option<(DateTime<Utc>, DateTime<Utc>)>, sqlx::Error>;
 }
 
 #[async_trait]
@@ -97,7 +124,22 @@ impl RoomMemberRepository for Db {
     async fn get_members(&self, room_id: Uuid) -> Result<Vec<RoomMember>, sqlx::Error> {
         sqlx::query_as::<_, RoomMember>(
             r#"
-            SELECT *
+            SELECT rm.*, u.avatar_file_id
+            FROM room_members rm
+            LEFT JOIN users u ON u.id = rm.user_id
+            WHERE rm.room_id = $1 AND rm.left_at IS NULL
+            "#,
+        )
+        .bind(room_id)
+        .fetch_all(self.pool())
+        .await
+    }
+
+    #[instrument(skip(self))]
+    async fn get_member_ids(&self, room_id: Uuid) -> Result<Vec<Uuid>, sqlx::Error> {
+        sqlx::query_scalar::<_, Uuid>(
+            r#"
+            SELECT user_id
             FROM room_members
             WHERE room_id = $1 AND left_at IS NULL
             "#,
@@ -107,6 +149,44 @@ impl RoomMemberRepository for Db {
         .await
     }
 
+    #[instrument(skip(self))]
+    async fn get_members_paginated(
+        &self,
+        room_id: Uuid,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<RoomMember>, sqlx::Error> {
+        sqlx::query_as::<_, RoomMember>(
+            r#"
+            SELECT rm.*, u.avatar_file_id
+            FROM room_members rm
+            LEFT JOIN users u ON u.id = rm.user_id
+            WHERE rm.room_id = $1 AND rm.left_at IS NULL
+            ORDER BY rm.joined_at, rm.id
+            LIMIT $2 OFFSET $3
+            "#,
+        )
+        .bind(room_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(self.pool())
+        .await
+    }
+
+    #[instrument(skip(self))]
+    async fn count_members(&self, room_id: Uuid) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar::<_, i64>(
+            r#"
+            SELECT COUNT(*)
+            FROM room_members
+            WHERE room_id = $1 AND left_at IS NULL
+            "#,
+        )
+        .bind(room_id)
+        .fetch_one(self.pool())
+        .await
+    }
+
     #[instrument(skip(self))]
     async fn is_member(&self, room_id: Uuid, user_id: Uuid) -> Result<bool, sqlx::Error> {
         let result = sqlx::query(
@@ -144,12 +224,23 @@ impl RoomMemberRepository for Db {
     async fn get_rooms_info_for_user(
         &self,
         user_id: Uuid,
-    ) -> Result<Vec<(RoomMember, Option<UserMessage>)>, sqlx::Error> {
+    ) -> Result<Vec<(RoomMember, Option<Room>, Option<UserMessage>)>, sqlx::Error> {
         sqlx::query(
             r#"
             SELECT * FROM (
                 SELECT DISTINCT ON (rm.room_id)
                     rm.id, rm.room_id, rm.room_name, rm.user_id, rm.username, rm.joined_at, rm.left_at, rm.last_read_at, rm.unread_count, rm.is_visible,
+                    r.id as r_id,
+                    r.name as r_name,
+                    r.creator_id as r_creator_id,
+                    r.creator_username as r_creator_username,
+                    r.admin_id as r_admin_id,
+                    r.admin_username as r_admin_username,
+                    r.created_at as r_created_at,
+                    r.description as r_description,
+                    r.avatar_file_id as r_avatar_file_id,
+                    r.message_count as r_message_count,
+                    r.is_direct as r_is_direct,
                     msg.id as message_id,
                     msg.room_id as msg_room_id,
                     msg.room_name as msg_room_name,
@@ -158,8 +249,14 @@ impl RoomMemberRepository for Db {
                     msg.content as msg_content,
                     msg.message_type as msg_message_type,
                     msg.status as msg_status,
-                    msg.created_at as msg_created_at
+                    msg.created_at as msg_created_at,
+                    msg.file_id as msg_file_id,
+                    msg.edited_at as msg_edited_at,
+                    msg.reply_to as msg_reply_to,
+                    msg.reply_count as msg_reply_count,
+                    msg.pinned as msg_pinned
                 FROM room_members rm
+                LEFT JOIN rooms r ON r.id = rm.room_id
                 LEFT JOIN LATERAL (
                     SELECT * FROM user_messages msg
                     WHERE msg.room_id = rm.room_id
@@ -177,6 +274,24 @@ impl RoomMemberRepository for Db {
         .bind(user_id)
         .try_map(|row| {
             let member = RoomMember::from_row(&row)?;
+            let room_id = row.try_get::<Option<Uuid>, _>("r_id").ok().flatten();
+
+            let room = match room_id {
+                Some(id) => Some(Room {
+                    id,
+                    name: row.try_get("r_name")?,
+                    creator_id: row.try_get("r_creator_id")?,
+                    creator_username: row.try_get("r_creator_username")?,
+                    admin_id: row.try_get("r_admin_id")?,
+                    admin_username: row.try_get("r_admin_username")?,
+                    created_at: row.try_get("r_created_at")?,
+                    description: row.try_get("r_description")?,
+                    avatar_file_id: row.try_get("r_avatar_file_id")?,
+                    message_count: row.try_get("r_message_count")?,
+                    is_direct: row.try_get("r_is_direct")?,
+                }),
+                None => None,
+            };
             let msg_id = row.try_get::<Option<Uuid>, _>("message_id").ok().flatten();
 
             let last_message = match msg_id {
@@ -193,17 +308,68 @@ impl RoomMemberRepository for Db {
                         message_type: row.try_get("msg_message_type")?,
                         status: row.try_get("msg_status")?,
                         created_at: row.try_get("msg_created_at")?,
+                        file_id: row.try_get("msg_file_id")?,
+                        edited_at: row.try_get("msg_edited_at")?,
+                        reply_to: row.try_get("msg_reply_to")?,
+                        reply_count: row.try_get("msg_reply_count")?,
+                        pinned: row.try_get("msg_pinned")?,
                     })
                 }
                 None => None,
             };
 
-            Ok((member, last_message))
+            Ok((member, room, last_message))
         })
         .fetch_all(self.pool())
         .await
     }
 
+    #[instrument(skip(self))]
+    async fn get_rooms_summary_for_user(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<RoomMember>, sqlx::Error> {
+        sqlx::query_as::<_, RoomMember>(
+            r#"
+            SELECT DISTINCT ON (room_id) *
+            FROM room_members
+            WHERE user_id = $1 AND is_visible = true
+            ORDER BY room_id, left_at DESC NULLS FIRST
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(self.pool())
+        .await
+    }
+
+    #[instrument(skip(self))]
+    async fn get_rooms_with_unread(&self, user_id: Uuid) -> Result<Vec<RoomMember>, sqlx::Error> {
+        sqlx::query_as::<_, RoomMember>(
+            r#"
+            SELECT * FROM room_members
+            WHERE user_id = $1 AND unread_count > 0 AND left_at IS NULL
+            ORDER BY unread_count DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(self.pool())
+        .await
+    }
+
+    #[instrument(skip(self))]
+    async fn get_current_memberships(&self, user_id: Uuid) -> Result<Vec<RoomMember>, sqlx::Error> {
+        sqlx::query_as::<_, RoomMember>(
+            r#"
+            SELECT * FROM room_members
+            WHERE user_id = $1 AND left_at IS NULL
+            ORDER BY joined_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(self.pool())
+        .await
+    }
+
     #[instrument(skip(self))]
     async fn increment_unread_count(
         &self,
@@ -230,21 +396,22 @@ impl RoomMemberRepository for Db {
         &self,
         room_id: Uuid,
         user_id: Uuid,
-    ) -> Result<(), sqlx::Error> {
-        sqlx::query(
+    ) -> Result<Option<(DateTime<Utc>, DateTime<Utc>)>, sqlx::Error> {
+        // The self-join reads the row as it was before the update
+        sqlx::query_as::<_, (DateTime<Utc>, DateTime<Utc>)>(
             r#"
-            UPDATE room_members
+            UPDATE room_members rm
             SET last_read_at = $1
             , unread_count = 0
-            WHERE room_id = $2 AND user_id = $3
+            FROM room_members prev
+            WHERE prev.id = rm.id AND rm.room_id = $2 AND rm.user_id = $3
+            RETURNING prev.last_read_at, rm.last_read_at
             "#,
         )
         .bind(Utc::now())
         .bind(room_id)
         .bind(user_id)
-        .execute(self.pool())
-        .await?;
-
-        Ok(())
+        .fetch_optional(self.pool())
+        .await
     }
 }
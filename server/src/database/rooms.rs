@@ -20,14 +20,53 @@ pub trait RoomRepository: Send + Sync {
 
     async fn get_room_by_id(&self, room_id: Uuid) -> Result<Option<Room>, sqlx::Error>;
 
+    /// Case-insensitive, ignoring `exclude_room_id` so a room can keep its own name
+    async fn creator_has_room_named(
+        &self,
+        creator_id: Uuid,
+        name: &str,
+        exclude_room_id: Option<Uuid>,
+    ) -> Result<bool, sqlx::Error>;
+
     async fn update_room_name(
         &self,
         room_id: Uuid,
         name: &str,
     ) -> Result<Option<Room>, sqlx::Error>;
 
+    async fn update_room_description(
+        &self,
+        room_id: Uuid,
+        description: Option<&str>,
+    ) -> Result<Option<Room>, sqlx::Error>;
+
+    async fn update_room_avatar(
+        &self,
+        room_id: Uuid,
+        avatar_file_id: Option<Uuid>,
+    ) -> Result<Option<Room>, sqlx::Error>;
+
+    /// Keeps the denormalized `admin_username` in step with `admin_id`
+    async fn set_admin(
+        &self,
+        room_id: Uuid,
+        new_admin_id: Uuid,
+    ) -> Result<Option<Room>, sqlx::Error>;
+
     async fn delete_room(&self, room_id: Uuid) -> Result<Option<Room>, sqlx::Error>;
 
+    /// The direct message room between two users, created with both as members
+    /// the first time; `true` when it was just created. Afterwards only
+    /// `user_a` is brought back in if they had left, as the other user
+    /// decides for themselves whether to rejoin.
+    async fn find_or_create_dm(
+        &self,
+        user_a: Uuid,
+        username_a: String,
+        user_b: Uuid,
+        username_b: String,
+    ) -> Result<(Room, bool), sqlx::Error>;
+
     async fn leave_room(
         &self,
         room_id: Uuid,
@@ -69,8 +108,8 @@ impl RoomRepository for Db {
 
         sqlx::query(
             r#"
-            INSERT INTO room_members (id, room_id, room_name, user_id, username, joined_at)
-            VALUES ($1, $2, $3, $4, $5, $6)
+            INSERT INTO room_members (id, room_id, room_name, user_id, username, joined_at, last_read_at, unread_count)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
             "#,
         )
         .bind(Uuid::new_v4())
@@ -79,6 +118,8 @@ impl RoomRepository for Db {
         .bind(creator_id)
         .bind(&creator_username)
         .bind(now)
+        .bind(now)
+        .bind(0)
         .execute(&mut *tx)
         .await?;
 
@@ -94,6 +135,30 @@ impl RoomRepository for Db {
             .await
     }
 
+    #[instrument(skip(self))]
+    async fn creator_has_room_named(
+        &self,
+        creator_id: Uuid,
+        name: &str,
+        exclude_room_id: Option<Uuid>,
+    ) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM rooms
+                WHERE creator_id = $1
+                AND LOWER(TRIM(name)) = LOWER(TRIM($2))
+                AND ($3::uuid IS NULL OR id != $3)
+            )
+            "#,
+        )
+        .bind(creator_id)
+        .bind(name)
+        .bind(exclude_room_id)
+        .fetch_one(self.pool())
+        .await
+    }
+
     #[instrument(skip(self))]
     async fn update_room_name(
         &self,
@@ -107,6 +172,55 @@ impl RoomRepository for Db {
             .await
     }
 
+    #[instrument(skip(self))]
+    async fn set_admin(
+        &self,
+        room_id: Uuid,
+        new_admin_id: Uuid,
+    ) -> Result<Option<Room>, sqlx::Error> {
+        sqlx::query_as::<_, Room>(
+            r#"
+            UPDATE rooms
+            SET admin_id = u.id, admin_username = u.username
+            FROM users u
+            WHERE rooms.id = $1 AND u.id = $2
+            RETURNING rooms.*
+            "#,
+        )
+        .bind(room_id)
+        .bind(new_admin_id)
+        .fetch_optional(self.pool())
+        .await
+    }
+
+    #[instrument(skip(self))]
+    async fn update_room_description(
+        &self,
+        room_id: Uuid,
+        description: Option<&str>,
+    ) -> Result<Option<Room>, sqlx::Error> {
+        sqlx::query_as::<_, Room>(r#"UPDATE rooms SET description = $1 WHERE id = $2 RETURNING *"#)
+            .bind(description)
+            .bind(room_id)
+            .fetch_optional(self.pool())
+            .await
+    }
+
+    #[instrument(skip(self))]
+    async fn update_room_avatar(
+        &self,
+        room_id: Uuid,
+        avatar_file_id: Option<Uuid>,
+    ) -> Result<Option<Room>, sqlx::Error> {
+        sqlx::query_as::<_, Room>(
+            r#"UPDATE rooms SET avatar_file_id = $1 WHERE id = $2 RETURNING *"#,
+        )
+        .bind(avatar_file_id)
+        .bind(room_id)
+        .fetch_optional(self.pool())
+        .await
+    }
+
     #[instrument(skip(self))]
     async fn delete_room(&self, room_id: Uuid) -> Result<Option<Room>, sqlx::Error> {
         sqlx::query_as::<_, Room>(r#"DELETE FROM rooms WHERE id = $1 RETURNING *"#)
@@ -115,6 +229,107 @@ impl RoomRepository for Db {
             .await
     }
 
+    #[instrument(skip(self))]
+    async fn find_or_create_dm(
+        &self,
+        user_a: Uuid,
+        username_a: String,
+        user_b: Uuid,
+        username_b: String,
+    ) -> Result<(Room, bool), sqlx::Error> {
+        let (user_low, user_high) = if user_a < user_b {
+            (user_a, user_b)
+        } else {
+            (user_b, user_a)
+        };
+        let now = Utc::now();
+        let mut tx = self.pool().begin().await?;
+
+        let existing = sqlx::query_as::<_, Room>(
+            r#"
+            SELECT r.*
+            FROM direct_rooms d
+            JOIN rooms r ON r.id = d.room_id
+            WHERE d.user_low = $1 AND d.user_high = $2
+            "#,
+        )
+        .bind(user_low)
+        .bind(user_high)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let (room, created) = match existing {
+            Some(room) => (room, false),
+            None => {
+                let room = sqlx::query_as::<_, Room>(
+                    r#"
+                    INSERT INTO rooms (id, name, creator_id, creator_username, admin_id, admin_username, created_at, is_direct)
+                    VALUES ($1, $2, $3, $4, $3, $4, $5, TRUE)
+                    RETURNING *
+                    "#,
+                )
+                .bind(Uuid::new_v4())
+                .bind(format!("{} & {}", username_a, username_b))
+                .bind(user_a)
+                .bind(&username_a)
+                .bind(now)
+                .fetch_one(&mut *tx)
+                .await?;
+
+                let claimed = sqlx::query_scalar::<_, Uuid>(
+                    r#"
+                    INSERT INTO direct_rooms (user_low, user_high, room_id)
+                    VALUES ($1, $2, $3)
+                    ON CONFLICT (user_low, user_high) DO NOTHING
+                    RETURNING room_id
+                    "#,
+                )
+                .bind(user_low)
+                .bind(user_high)
+                .bind(room.id)
+                .fetch_optional(&mut *tx)
+                .await?;
+
+                // A concurrent start created the room first; use that one
+                if claimed.is_none() {
+                    tx.rollback().await?;
+                    return self
+                        .find_or_create_dm(user_a, username_a, user_b, username_b)
+                        .await;
+                }
+                (room, true)
+            }
+        };
+
+        let mut joining = vec![(user_a, &username_a)];
+        if created {
+            joining.push((user_b, &username_b));
+        }
+        for (user_id, username) in joining {
+            sqlx::query(
+                r#"
+                INSERT INTO room_members (id, room_id, room_name, user_id, username, joined_at, last_read_at, unread_count)
+                SELECT $1, $2, $3, $4, $5, $6, $6, 0
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM room_members
+                    WHERE room_id = $2 AND user_id = $4 AND left_at IS NULL
+                )
+                "#,
+            )
+            .bind(Uuid::new_v4())
+            .bind(room.id)
+            .bind(&room.name)
+            .bind(user_id)
+            .bind(username)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok((room, created))
+    }
+
     #[instrument(skip(self))]
     async fn leave_room(
         &self,
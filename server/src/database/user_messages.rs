@@ -1,15 +1,17 @@
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use tracing::instrument;
 use uuid::Uuid;
 
 use crate::database::{
     db::Db,
-    models::{MessageStatus, MessageType, UserMessage},
+    files::NewFile,
+    models::{FileRecord, MessageStatus, MessageType, UserMessage},
 };
 
 #[async_trait]
 pub trait MessageRepository: Send + Sync {
+    #[allow(clippy::too_many_arguments)]
     async fn insert_message(
         &self,
         room_id: Uuid,
@@ -18,8 +20,22 @@ pub trait MessageRepository: Send + Sync {
         author_username: Option<String>,
         content: &str,
         message_type: MessageType,
+        file_id: Option<Uuid>,
+        reply_to: Option<Uuid>,
     ) -> Result<UserMessage, sqlx::Error>;
 
+    /// Stores an upload and the file message pointing at it atomically, so a
+    /// failure can't leave either one behind
+    async fn insert_file_message(
+        &self,
+        file: NewFile,
+        room_id: Uuid,
+        room_name: String,
+        author_id: Uuid,
+        author_username: String,
+        content: &str,
+    ) -> Result<(FileRecord, UserMessage), sqlx::Error>;
+
     async fn get_message_by_id(&self, message_id: Uuid)
     -> Result<Option<UserMessage>, sqlx::Error>;
 
@@ -31,6 +47,46 @@ pub trait MessageRepository: Send + Sync {
         offset: i64,
     ) -> Result<Vec<UserMessage>, sqlx::Error>;
 
+    /// Keyset page ending just before the message at `before`, compared on
+    /// `(created_at, id)` so messages sharing a timestamp aren't skipped
+    async fn get_room_messages_before(
+        &self,
+        room_id: Uuid,
+        user_id: Uuid,
+        before: Option<(DateTime<Utc>, Uuid)>,
+        limit: i64,
+    ) -> Result<Vec<UserMessage>, sqlx::Error>;
+
+    async fn count_room_messages(&self, room_id: Uuid, user_id: Uuid) -> Result<i64, sqlx::Error>;
+
+    /// Newest first, across every room whose history this user can see, or
+    /// only `room_id` if given
+    async fn search_messages(
+        &self,
+        user_id: Uuid,
+        room_id: Option<Uuid>,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<UserMessage>, sqlx::Error>;
+
+    /// Every message whose reply chain leads back to `root_id`, oldest first,
+    /// limited to what this member could see in the room's history
+    async fn get_thread_messages(
+        &self,
+        root_id: Uuid,
+        user_id: Uuid,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<UserMessage>, sqlx::Error>;
+
+    /// Number of messages in the reply chain ending at `message_id`, walking
+    /// at most `max_depth` steps up; `None` if the chain loops back on itself
+    async fn get_reply_depth(
+        &self,
+        message_id: Uuid,
+        max_depth: i32,
+    ) -> Result<Option<i32>, sqlx::Error>;
+
     async fn update_message_content(
         &self,
         message_id: Uuid,
@@ -38,10 +94,25 @@ pub trait MessageRepository: Send + Sync {
     ) -> Result<Option<UserMessage>, sqlx::Error>;
 
     async fn delete_message(&self, message_id: Uuid) -> Result<Option<UserMessage>, sqlx::Error>;
+
+    /// `None` if the message is deleted, missing or already pinned
+    async fn pin_message(&self, message_id: Uuid) -> Result<Option<UserMessage>, sqlx::Error>;
+
+    /// `None` if the message is missing or wasn't pinned
+    async fn unpin_message(&self, message_id: Uuid) -> Result<Option<UserMessage>, sqlx::Error>;
+
+    /// Pinned messages that haven't been deleted since, oldest first, limited
+    /// to what this member could see in the room's history
+    async fn get_pinned_messages(
+        &self,
+        room_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Vec<UserMessage>, sqlx::Error>;
 }
 
 #[async_trait]
 impl MessageRepository for Db {
+    #[allow(clippy::too_many_arguments)]
     #[instrument(skip(self))]
     async fn insert_message(
         &self,
@@ -51,11 +122,17 @@ impl MessageRepository for Db {
         author_username: Option<String>,
         content: &str,
         message_type: MessageType,
+        file_id: Option<Uuid>,
+        reply_to: Option<Uuid>,
     ) -> Result<UserMessage, sqlx::Error> {
+        // The parent's counter is bumped in the same statement as the insert
         sqlx::query_as::<_, UserMessage>(
             r#"
-            INSERT INTO user_messages (id, room_id, room_name, author_id, author_username, content, message_type, status, created_at)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            WITH parent AS (
+                UPDATE user_messages SET reply_count = reply_count + 1 WHERE id = $11
+            )
+            INSERT INTO user_messages (id, room_id, room_name, author_id, author_username, content, message_type, status, created_at, file_id, reply_to)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
             RETURNING *
             "#,
         )
@@ -68,10 +145,66 @@ impl MessageRepository for Db {
         .bind(message_type)
         .bind(MessageStatus::Sent)
         .bind(Utc::now())
+        .bind(file_id)
+        .bind(reply_to)
         .fetch_one(self.pool())
         .await
     }
 
+    #[instrument(skip(self, file))]
+    async fn insert_file_message(
+        &self,
+        file: NewFile,
+        room_id: Uuid,
+        room_name: String,
+        author_id: Uuid,
+        author_username: String,
+        content: &str,
+    ) -> Result<(FileRecord, UserMessage), sqlx::Error> {
+        let now = Utc::now();
+        let mut tx = self.pool().begin().await?;
+
+        let file = sqlx::query_as::<_, FileRecord>(
+            r#"
+            INSERT INTO files (id, encrypted_data, encrypted_metadata, size_in_bytes, file_hash, uploaded_at, uploader_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(file.encrypted_data)
+        .bind(file.encrypted_metadata)
+        .bind(file.size_in_bytes)
+        .bind(file.file_hash)
+        .bind(now)
+        .bind(file.uploader_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let message = sqlx::query_as::<_, UserMessage>(
+            r#"
+            INSERT INTO user_messages (id, room_id, room_name, author_id, author_username, content, message_type, status, created_at, file_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(room_id)
+        .bind(room_name)
+        .bind(author_id)
+        .bind(author_username)
+        .bind(content)
+        .bind(MessageType::File)
+        .bind(MessageStatus::Sent)
+        .bind(now)
+        .bind(file.id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok((file, message))
+    }
+
     #[instrument(skip(self))]
     async fn get_message_by_id(
         &self,
@@ -103,7 +236,12 @@ impl MessageRepository for Db {
                 m.id, m.room_id, m.room_name, m.author_id, m.author_username, m.content,
                 m.message_type,
                 m.status,
-                m.created_at
+                m.created_at,
+                m.file_id,
+                m.edited_at,
+                m.reply_to,
+                m.reply_count,
+                m.pinned
             FROM user_messages m
             JOIN room_members rm ON m.room_id = rm.room_id
             WHERE m.room_id = $1
@@ -125,6 +263,178 @@ impl MessageRepository for Db {
         Ok(messages)
     }
 
+    #[instrument(skip(self))]
+    async fn get_room_messages_before(
+        &self,
+        room_id: Uuid,
+        user_id: Uuid,
+        before: Option<(DateTime<Utc>, Uuid)>,
+        limit: i64,
+    ) -> Result<Vec<UserMessage>, sqlx::Error> {
+        let (before_at, before_id) = before.unzip();
+        let mut messages = sqlx::query_as::<_, UserMessage>(
+            r#"
+            SELECT
+                m.id, m.room_id, m.room_name, m.author_id, m.author_username, m.content,
+                m.message_type,
+                m.status,
+                m.created_at,
+                m.file_id,
+                m.edited_at,
+                m.reply_to,
+                m.reply_count,
+                m.pinned
+            FROM user_messages m
+            JOIN room_members rm ON m.room_id = rm.room_id
+            WHERE m.room_id = $1
+            AND rm.user_id = $2
+            AND (rm.left_at IS NULL OR m.created_at <= rm.left_at)
+            AND m.created_at >= rm.joined_at
+            AND ($3::timestamptz IS NULL OR (m.created_at, m.id) < ($3, $4))
+            ORDER BY m.created_at DESC, m.id DESC
+            LIMIT $5
+            "#,
+        )
+        .bind(room_id)
+        .bind(user_id)
+        .bind(before_at)
+        .bind(before_id)
+        .bind(limit)
+        .fetch_all(self.pool())
+        .await?;
+
+        messages.reverse();
+        Ok(messages)
+    }
+
+    /// Counts the messages `get_room_messages` can page through for this member
+    #[instrument(skip(self))]
+    async fn count_room_messages(&self, room_id: Uuid, user_id: Uuid) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar::<_, i64>(
+            r#"
+            SELECT COUNT(*)
+            FROM user_messages m
+            JOIN room_members rm ON m.room_id = rm.room_id
+            WHERE m.room_id = $1
+            AND rm.user_id = $2
+            AND (rm.left_at IS NULL OR m.created_at <= rm.left_at)
+            AND m.created_at >= rm.joined_at
+            "#,
+        )
+        .bind(room_id)
+        .bind(user_id)
+        .fetch_one(self.pool())
+        .await
+    }
+
+    #[instrument(skip(self))]
+    async fn search_messages(
+        &self,
+        user_id: Uuid,
+        room_id: Option<Uuid>,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<UserMessage>, sqlx::Error> {
+        let pattern = format!("%{}%", query);
+        // System messages hold JSON, so only user content is searched
+        sqlx::query_as::<_, UserMessage>(
+            r#"
+            SELECT
+                m.id, m.room_id, r.name AS room_name, m.author_id, m.author_username, m.content,
+                m.message_type,
+                m.status,
+                m.created_at,
+                m.file_id,
+                m.edited_at,
+                m.reply_to,
+                m.reply_count,
+                m.pinned
+            FROM user_messages m
+            JOIN room_members rm ON m.room_id = rm.room_id
+            JOIN rooms r ON r.id = m.room_id
+            WHERE rm.user_id = $1
+            AND ($2::uuid IS NULL OR m.room_id = $2)
+            AND (rm.left_at IS NULL OR m.created_at <= rm.left_at)
+            AND m.created_at >= rm.joined_at
+            AND m.message_type != 'system'
+            AND m.status != 'deleted'
+            AND m.content ILIKE $3
+            ORDER BY m.created_at DESC
+            LIMIT $4
+            "#,
+        )
+        .bind(user_id)
+        .bind(room_id)
+        .bind(pattern)
+        .bind(limit)
+        .fetch_all(self.pool())
+        .await
+    }
+
+    #[instrument(skip(self))]
+    async fn get_thread_messages(
+        &self,
+        root_id: Uuid,
+        user_id: Uuid,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<UserMessage>, sqlx::Error> {
+        sqlx::query_as::<_, UserMessage>(
+            r#"
+            WITH RECURSIVE thread(id, path) AS (
+                SELECT id, ARRAY[id] FROM user_messages
+                WHERE reply_to = $1 AND id != $1
+                UNION ALL
+                SELECT m.id, t.path || m.id FROM user_messages m
+                JOIN thread t ON m.reply_to = t.id
+                WHERE m.id != $1 AND NOT m.id = ANY(t.path)
+            )
+            SELECT m.*
+            FROM thread t
+            JOIN user_messages m ON m.id = t.id
+            JOIN room_members rm ON m.room_id = rm.room_id
+            WHERE rm.user_id = $2
+            AND (rm.left_at IS NULL OR m.created_at <= rm.left_at)
+            AND m.created_at >= rm.joined_at
+            ORDER BY m.created_at ASC
+            LIMIT $3 OFFSET $4
+            "#,
+        )
+        .bind(root_id)
+        .bind(user_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(self.pool())
+        .await
+    }
+
+    #[instrument(skip(self))]
+    async fn get_reply_depth(
+        &self,
+        message_id: Uuid,
+        max_depth: i32,
+    ) -> Result<Option<i32>, sqlx::Error> {
+        sqlx::query_scalar::<_, Option<i32>>(
+            r#"
+            WITH RECURSIVE chain(id, reply_to, depth, path, is_cycle) AS (
+                SELECT id, reply_to, 1, ARRAY[id], false
+                FROM user_messages WHERE id = $1
+                UNION ALL
+                SELECT m.id, m.reply_to, c.depth + 1, c.path || m.id, m.id = ANY(c.path)
+                FROM user_messages m
+                JOIN chain c ON m.id = c.reply_to
+                WHERE NOT c.is_cycle AND c.depth < $2
+            )
+            SELECT CASE WHEN bool_or(is_cycle) THEN NULL ELSE MAX(depth) END
+            FROM chain
+            "#,
+        )
+        .bind(message_id)
+        .bind(max_depth)
+        .fetch_one(self.pool())
+        .await
+    }
+
     #[instrument(skip(self))]
     async fn update_message_content(
         &self,
@@ -134,7 +444,7 @@ impl MessageRepository for Db {
         sqlx::query_as::<_, UserMessage>(
             r#"
             UPDATE user_messages
-            SET content = $1, status = 'edited'
+            SET content = $1, status = 'edited', edited_at = NOW()
             WHERE id = $2 AND status != 'deleted'
             RETURNING *
             "#,
@@ -159,4 +469,58 @@ impl MessageRepository for Db {
         .fetch_optional(self.pool())
         .await
     }
+
+    #[instrument(skip(self))]
+    async fn pin_message(&self, message_id: Uuid) -> Result<Option<UserMessage>, sqlx::Error> {
+        sqlx::query_as::<_, UserMessage>(
+            r#"
+            UPDATE user_messages
+            SET pinned = true
+            WHERE id = $1 AND status != 'deleted' AND NOT pinned
+            RETURNING *
+            "#,
+        )
+        .bind(message_id)
+        .fetch_optional(self.pool())
+        .await
+    }
+
+    #[instrument(skip(self))]
+    async fn unpin_message(&self, message_id: Uuid) -> Result<Option<UserMessage>, sqlx::Error> {
+        sqlx::query_as::<_, UserMessage>(
+            r#"
+            UPDATE user_messages
+            SET pinned = false
+            WHERE id = $1 AND pinned
+            RETURNING *
+            "#,
+        )
+        .bind(message_id)
+        .fetch_optional(self.pool())
+        .await
+    }
+
+    #[instrument(skip(self))]
+    async fn get_pinned_messages(
+        &self,
+        room_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Vec<UserMessage>, sqlx::Error> {
+        sqlx::query_as::<_, UserMessage>(
+            r#"
+            SELECT m.* FROM user_messages m
+            JOIN room_members rm ON m.room_id = rm.room_id
+            WHERE m.room_id = $1
+            AND rm.user_id = $2
+            AND (rm.left_at IS NULL OR m.created_at <= rm.left_at)
+            AND m.created_at >= rm.joined_at
+            AND m.pinned AND m.status != 'deleted'
+            ORDER BY m.created_at ASC
+            "#,
+        )
+        .bind(room_id)
+        .bind(user_id)
+        .fetch_all(self.pool())
+        .await
+    }
 }
@@ -1,5 +1,5 @@
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{Duration, Utc};
 use tracing::instrument;
 use uuid::Uuid;
 
@@ -19,8 +19,47 @@ pub trait UserRepository: Send + Sync {
         role: UserRole,
     ) -> Result<Option<User>, sqlx::Error>;
     async fn delete_user(&self, id: Uuid) -> Result<Option<User>, sqlx::Error>;
-    async fn search_users(&self, query: &str) -> Result<Vec<User>, sqlx::Error>;
+    /// Ordered by username so pages don't overlap. Leaves out `searcher_id`
+    /// and, given `exclude_room_id`, that room's current members.
+    async fn search_users(
+        &self,
+        query: &str,
+        searcher_id: Uuid,
+        exclude_room_id: Option<Uuid>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<User>, sqlx::Error>;
     async fn update_key_backup(&self, user_id: Uuid, backup: &str) -> Result<(), sqlx::Error>;
+    async fn update_avatar(
+        &self,
+        user_id: Uuid,
+        avatar_file_id: Option<Uuid>,
+    ) -> Result<Option<User>, sqlx::Error>;
+    /// Marks the new address unverified and drops any outstanding
+    /// verification tokens for the old one.
+    async fn update_recovery_email(
+        &self,
+        user_id: Uuid,
+        recovery_email: Option<&str>,
+    ) -> Result<Option<User>, sqlx::Error>;
+    async fn insert_recovery_email_token(
+        &self,
+        user_id: Uuid,
+        token_hash: &str,
+        expires_in: Duration,
+    ) -> Result<(), sqlx::Error>;
+    /// Consumes the token and marks the user's recovery email verified.
+    /// Returns `None` if the token is unknown, expired or someone else's.
+    async fn verify_recovery_email(
+        &self,
+        user_id: Uuid,
+        token_hash: &str,
+    ) -> Result<Option<User>, sqlx::Error>;
+    async fn update_role(
+        &self,
+        username: &str,
+        role: UserRole,
+    ) -> Result<Option<User>, sqlx::Error>;
 }
 
 #[async_trait]
@@ -74,16 +113,34 @@ impl UserRepository for Db {
     }
 
     #[instrument(skip(self))]
-    async fn search_users(&self, query: &str) -> Result<Vec<User>, sqlx::Error> {
+    async fn search_users(
+        &self,
+        query: &str,
+        searcher_id: Uuid,
+        exclude_room_id: Option<Uuid>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<User>, sqlx::Error> {
         let pattern = format!("%{}%", query);
+        // Filtered in the query so pages stay full and `LIMIT` stays accurate
         sqlx::query_as::<_, User>(
             r#"
-            SELECT * FROM users
-            WHERE username ILIKE $1
-            LIMIT 20
+            SELECT * FROM users u
+            WHERE u.username ILIKE $1
+            AND u.id != $2
+            AND NOT EXISTS (
+                SELECT 1 FROM room_members rm
+                WHERE rm.room_id = $3 AND rm.user_id = u.id AND rm.left_at IS NULL
+            )
+            ORDER BY u.username, u.id
+            LIMIT $4 OFFSET $5
             "#,
         )
         .bind(pattern)
+        .bind(searcher_id)
+        .bind(exclude_room_id)
+        .bind(limit)
+        .bind(offset)
         .fetch_all(self.pool())
         .await
     }
@@ -97,4 +154,123 @@ impl UserRepository for Db {
             .await?;
         Ok(())
     }
+
+    #[instrument(skip(self, recovery_email))]
+    async fn update_recovery_email(
+        &self,
+        user_id: Uuid,
+        recovery_email: Option<&str>,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let mut tx = self.pool().begin().await?;
+
+        let user = sqlx::query_as::<_, User>(
+            r#"
+            UPDATE users
+            SET recovery_email = $1, recovery_email_verified_at = NULL
+            WHERE id = $2
+            RETURNING *
+            "#,
+        )
+        .bind(recovery_email)
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        sqlx::query(r#"DELETE FROM recovery_email_tokens WHERE user_id = $1"#)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(user)
+    }
+
+    #[instrument(skip(self, token_hash))]
+    async fn insert_recovery_email_token(
+        &self,
+        user_id: Uuid,
+        token_hash: &str,
+        expires_in: Duration,
+    ) -> Result<(), sqlx::Error> {
+        let created_at = Utc::now();
+
+        sqlx::query(
+            r#"
+            INSERT INTO recovery_email_tokens (id, user_id, token_hash, expires_at, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(token_hash)
+        .bind(created_at + expires_in)
+        .bind(created_at)
+        .execute(self.pool())
+        .await?;
+        Ok(())
+    }
+
+    #[instrument(skip(self, token_hash))]
+    async fn verify_recovery_email(
+        &self,
+        user_id: Uuid,
+        token_hash: &str,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let mut tx = self.pool().begin().await?;
+
+        let consumed = sqlx::query(
+            r#"
+            DELETE FROM recovery_email_tokens
+            WHERE token_hash = $1 AND user_id = $2 AND expires_at > NOW()
+            "#,
+        )
+        .bind(token_hash)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        if consumed == 0 {
+            tx.commit().await?;
+            return Ok(None);
+        }
+
+        let user = sqlx::query_as::<_, User>(
+            r#"UPDATE users SET recovery_email_verified_at = NOW() WHERE id = $1 RETURNING *"#,
+        )
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(user)
+    }
+
+    #[instrument(skip(self))]
+    async fn update_avatar(
+        &self,
+        user_id: Uuid,
+        avatar_file_id: Option<Uuid>,
+    ) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as::<_, User>(
+            r#"UPDATE users SET avatar_file_id = $1 WHERE id = $2 RETURNING *"#,
+        )
+        .bind(avatar_file_id)
+        .bind(user_id)
+        .fetch_optional(self.pool())
+        .await
+    }
+
+    #[instrument(skip(self))]
+    async fn update_role(
+        &self,
+        username: &str,
+        role: UserRole,
+    ) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as::<_, User>(r#"UPDATE users SET role = $1 WHERE username = $2 RETURNING *"#)
+            .bind(role)
+            .bind(username)
+            .fetch_optional(self.pool())
+            .await
+    }
 }
@@ -3,11 +3,19 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
-    database::models::{InvitationStatus, MessageStatus, MessageType, UserRole},
+    database::models::{
+        InvitationStatus, MessageStatus, MessageType, RoomMember, UserMessage, UserRole,
+    },
     errors::error::ApiErrorItem,
-    utils::validation::{validate_confirm_password, validate_password, validate_username},
+    utils::validation::{
+        validate_confirm_password, validate_password, validate_recovery_email, validate_username,
+    },
 };
 
+// All timestamps are `DateTime<Utc>` and go over the wire as RFC3339 in UTC with a
+// trailing `Z` (e.g. "2025-01-01T12:00:00.123456Z"). Clients parse this format, so
+// don't switch a field to a local offset or a custom serializer.
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct RegisterReqDto {
     pub username: String,
@@ -77,6 +85,151 @@ pub struct RefreshTokenRespDto {
     pub refresh_token: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogoutAllRespDto {
+    /// Refresh tokens deleted, rotated ones included
+    pub revoked: u64,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SetRecoveryEmailReqDto {
+    pub recovery_email: Option<String>,
+}
+
+impl SetRecoveryEmailReqDto {
+    pub fn validate(&self) -> Result<(), Vec<ApiErrorItem>> {
+        let mut errors = Vec::new();
+
+        if let Some(email) = &self.recovery_email {
+            errors.extend(validate_recovery_email(email));
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoveryEmailRespDto {
+    pub recovery_email: Option<String>,
+    /// Password resets are only sent to a verified address
+    pub recovery_email_verified: bool,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct VerifyRecoveryEmailReqDto {
+    pub token: String,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SetAvatarReqDto {
+    pub file_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AvatarRespDto {
+    pub avatar_file_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteOrphanFilesRespDto {
+    pub deleted: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdminDisconnectReqDto {
+    pub user_id: Uuid,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GrantRoleReqDto {
+    pub username: String,
+    pub role: UserRole,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GrantRoleRespDto {
+    pub username: String,
+    pub role: UserRole,
+}
+
+/// What support needs to know about a user; never carries credentials
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdminUserSummaryRespDto {
+    pub id: Uuid,
+    pub username: String,
+    pub role: UserRole,
+    pub created_at: DateTime<Utc>,
+    pub avatar_file_id: Option<Uuid>,
+    pub has_recovery_email: bool,
+    /// Open WebSocket connections right now
+    pub connections: usize,
+    pub rooms: Vec<AdminUserRoomDto>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdminUserRoomDto {
+    pub room_id: Uuid,
+    pub room_name: String,
+    pub joined_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdminDisconnectRespDto {
+    /// Whether the user had an open connection
+    pub disconnected: bool,
+}
+
+/// Optional features a client may need to hide or disable
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FeaturesRespDto {
+    pub password_reset: bool,
+    pub file_upload: bool,
+    pub reactions: bool,
+    pub drafts: bool,
+    pub pins: bool,
+    pub direct_messages: bool,
+}
+
+/// The running build; `git_sha` is `unknown` when built outside a checkout
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionRespDto {
+    pub version: String,
+    pub git_sha: String,
+    pub build_time: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PasswordResetReqDto {
+    pub username: String,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PasswordResetConfirmReqDto {
+    pub token: String,
+    pub password: String,
+    pub confirm_password: String,
+}
+
+impl PasswordResetConfirmReqDto {
+    pub fn validate(&self) -> Result<(), Vec<ApiErrorItem>> {
+        let mut errors = Vec::new();
+
+        errors.extend(validate_password(&self.password));
+        errors.extend(validate_confirm_password(
+            &self.password,
+            &self.confirm_password,
+        ));
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UploadFileRespDto {
     pub file_id: Uuid,
@@ -107,6 +260,12 @@ pub struct UploadKeysReqDto {
     pub one_time_prekeys: Vec<OneTimePreKeyDto>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UploadKeysRespDto {
+    /// One-time prekeys stored from this upload
+    pub accepted_one_time_keys: usize,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PreKeyBundleRespDto {
     pub identity_key: String,
@@ -115,9 +274,24 @@ pub struct PreKeyBundleRespDto {
     pub one_time_prekey: Option<OneTimePreKeyDto>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserPreKeyBundleDto {
+    pub username: String,
+    #[serde(flatten)]
+    pub bundle: PreKeyBundleRespDto,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RoomPreKeyBundlesRespDto {
+    pub bundles: Vec<UserPreKeyBundleDto>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct KeyCountRespDto {
     pub count: i64,
+    /// Set once `count` drops below the server's low watermark, so clients
+    /// can refill before bundles run out of one-time prekeys
+    pub needs_replenishment: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -150,23 +324,53 @@ pub enum ClientReq {
     CreateRoom {
         name: String,
     },
+    /// Opens the caller's direct message room with `username`, creating it
+    /// the first time
+    StartDirectMessage {
+        username: String,
+    },
     JoinRoom {
         invitation_id: Uuid,
     },
+    AcceptInvitations {
+        invitation_ids: Vec<Uuid>,
+    },
     LeaveRoom {
         room_id: Uuid,
     },
+    LeaveRooms {
+        room_ids: Vec<Uuid>,
+    },
     UpdateRoom {
         room_id: Uuid,
         name: String,
     },
+    SetRoomDescription {
+        room_id: Uuid,
+        description: Option<String>,
+    },
+    SetRoomAvatar {
+        room_id: Uuid,
+        file_id: Option<Uuid>,
+    },
     DeleteRoom {
         room_id: Uuid,
     },
+    /// Hands the room's admin rights to another member
+    TransferAdmin {
+        room_id: Uuid,
+        username: String,
+    },
     GetRoomInfo {
         room_id: Uuid,
     },
+    GetRoomMembers {
+        room_id: Uuid,
+        limit: i64,
+        offset: i64,
+    },
     GetRoomsInfo,
+    GetRoomsSummary,
     Invite {
         room_id: Uuid,
         username: String,
@@ -175,31 +379,188 @@ pub enum ClientReq {
         invitation_id: Uuid,
     },
     GetPendingInvitations,
+    GetPendingInvitationCount,
+    GetRoomInvitations {
+        room_id: Uuid,
+    },
+    PreviewRoom {
+        invitation_id: Uuid,
+    },
     SendMessage {
         room_id: Uuid,
         content: String,
         message_type: Option<MessageType>,
+        file_id: Option<Uuid>,
+        reply_to: Option<Uuid>,
     },
     EditMessage {
         message_id: Uuid,
         new_content: String,
     },
+    /// Sent while the user types; `is_typing: false` clears it early
+    Typing {
+        room_id: Uuid,
+        #[serde(default = "default_is_typing")]
+        is_typing: bool,
+    },
     DeleteMessage {
         message_id: Uuid,
     },
+    React {
+        message_id: Uuid,
+        emoji: String,
+    },
+    Unreact {
+        message_id: Uuid,
+        emoji: String,
+    },
+    /// Admins of the message's room only
+    PinMessage {
+        message_id: Uuid,
+    },
+    UnpinMessage {
+        message_id: Uuid,
+    },
+    GetPinnedMessages {
+        room_id: Uuid,
+    },
     GetMessages {
         room_id: Uuid,
         limit: i64,
         offset: i64,
     },
+    /// Page backwards from `before`, the previous page's `next_cursor`;
+    /// without one, starts from the newest message
+    GetMessagesBefore {
+        room_id: Uuid,
+        before: Option<String>,
+        limit: i64,
+    },
+    GetThread {
+        root_message_id: Uuid,
+        limit: i64,
+        offset: i64,
+    },
     DeleteAccount,
     KickMember {
         room_id: Uuid,
         username: String,
     },
+    /// `limit` defaults to the configured search limit; both may be omitted.
+    /// The caller is never listed, nor are members of `exclude_room_id`.
     SearchUsers {
         query: String,
+        limit: Option<i64>,
+        offset: Option<i64>,
+        exclude_room_id: Option<Uuid>,
+    },
+    SearchAllMessages {
+        query: String,
+        limit: i64,
+    },
+    SearchMessages {
+        room_id: Uuid,
+        query: String,
+        limit: i64,
+    },
+    /// Replaces the caller's draft for the room; `content` is opaque, so
+    /// clients may store it encrypted
+    SaveDraft {
+        room_id: Uuid,
+        content: String,
+    },
+    GetDraft {
+        room_id: Uuid,
     },
+    ConnectionInfo,
+    /// Any `type` this server doesn't know, e.g. from a newer client
+    #[serde(other)]
+    Unknown,
+}
+
+fn default_is_typing() -> bool {
+    true
+}
+
+impl ClientReq {
+    /// Wire name of the request, as in its `type` tag
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ClientReq::CreateRoom { .. } => "create_room",
+            ClientReq::StartDirectMessage { .. } => "start_direct_message",
+            ClientReq::JoinRoom { .. } => "join_room",
+            ClientReq::AcceptInvitations { .. } => "accept_invitations",
+            ClientReq::LeaveRoom { .. } => "leave_room",
+            ClientReq::LeaveRooms { .. } => "leave_rooms",
+            ClientReq::UpdateRoom { .. } => "update_room",
+            ClientReq::SetRoomDescription { .. } => "set_room_description",
+            ClientReq::SetRoomAvatar { .. } => "set_room_avatar",
+            ClientReq::DeleteRoom { .. } => "delete_room",
+            ClientReq::TransferAdmin { .. } => "transfer_admin",
+            ClientReq::GetRoomInfo { .. } => "get_room_info",
+            ClientReq::GetRoomMembers { .. } => "get_room_members",
+            ClientReq::GetRoomsInfo => "get_rooms_info",
+            ClientReq::GetRoomsSummary => "get_rooms_summary",
+            ClientReq::Invite { .. } => "invite",
+            ClientReq::DeclineInvitation { .. } => "decline_invitation",
+            ClientReq::GetPendingInvitations => "get_pending_invitations",
+            ClientReq::GetPendingInvitationCount => "get_pending_invitation_count",
+            ClientReq::GetRoomInvitations { .. } => "get_room_invitations",
+            ClientReq::PreviewRoom { .. } => "preview_room",
+            ClientReq::SendMessage { .. } => "send_message",
+            ClientReq::EditMessage { .. } => "edit_message",
+            ClientReq::Typing { .. } => "typing",
+            ClientReq::DeleteMessage { .. } => "delete_message",
+            ClientReq::React { .. } => "react",
+            ClientReq::Unreact { .. } => "unreact",
+            ClientReq::PinMessage { .. } => "pin_message",
+            ClientReq::UnpinMessage { .. } => "unpin_message",
+            ClientReq::GetPinnedMessages { .. } => "get_pinned_messages",
+            ClientReq::GetMessages { .. } => "get_messages",
+            ClientReq::GetMessagesBefore { .. } => "get_messages_before",
+            ClientReq::GetThread { .. } => "get_thread",
+            ClientReq::DeleteAccount => "delete_account",
+            ClientReq::KickMember { .. } => "kick_member",
+            ClientReq::SearchUsers { .. } => "search_users",
+            ClientReq::SearchAllMessages { .. } => "search_all_messages",
+            ClientReq::SearchMessages { .. } => "search_messages",
+            ClientReq::SaveDraft { .. } => "save_draft",
+            ClientReq::GetDraft { .. } => "get_draft",
+            ClientReq::ConnectionInfo => "connection_info",
+            ClientReq::Unknown => "unknown",
+        }
+    }
+}
+
+/// Bumped on breaking changes to the WebSocket protocol
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Version `n` is offered as the `Sec-WebSocket-Protocol` `chat.v{n}`
+pub const SUBPROTOCOL_PREFIX: &str = "chat.v";
+
+/// Every subprotocol this server speaks, newest first
+pub fn supported_subprotocols() -> Vec<String> {
+    (1..=PROTOCOL_VERSION)
+        .rev()
+        .map(|version| format!("{}{}", SUBPROTOCOL_PREFIX, version))
+        .collect()
+}
+
+/// What actually goes over the socket: the event plus a top-level `v`
+#[derive(Serialize, Debug)]
+pub struct ServerEnvelope<'a> {
+    pub v: u32,
+    #[serde(flatten)]
+    pub event: &'a ServerResp,
+}
+
+impl<'a> ServerEnvelope<'a> {
+    pub fn new(event: &'a ServerResp) -> Self {
+        Self {
+            v: PROTOCOL_VERSION,
+            event,
+        }
+    }
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -210,6 +571,13 @@ pub enum ServerResp {
         room_name: String,
         created_at: DateTime<Utc>,
     },
+    /// Sent to both sides; `username` is the other one
+    DirectMessageStarted {
+        room_id: Uuid,
+        room_name: String,
+        username: String,
+        created_at: DateTime<Utc>,
+    },
     RoomJoined {
         invitation_id: Uuid,
         room_id: Uuid,
@@ -219,40 +587,80 @@ pub enum ServerResp {
         created_at: DateTime<Utc>,
         joined_at: DateTime<Utc>,
     },
+    /// Each joined room also gets its own `RoomJoined`
+    InvitationsAccepted {
+        joined: Vec<JoinedRoomInfo>,
+        failed: Vec<FailedInvitation>,
+    },
     RoomLeft {
         room_id: Uuid,
         room_name: String,
     },
+    /// Each left room also gets its own `RoomLeft`
+    RoomsLeft {
+        left: Vec<Uuid>,
+        failed: Vec<FailedRoom>,
+    },
     RoomUpdated {
         room_id: Uuid,
         room_name: String,
     },
+    RoomDescriptionUpdated {
+        room_id: Uuid,
+        room_name: String,
+        description: Option<String>,
+    },
+    RoomAvatarUpdated {
+        room_id: Uuid,
+        room_name: String,
+        avatar_file_id: Option<Uuid>,
+    },
     RoomDeleted {
         room_id: Uuid,
         room_name: String,
     },
+    AdminTransferred {
+        room_id: Uuid,
+        room_name: String,
+        new_admin_username: String,
+    },
     RoomInfo {
         room_id: Uuid,
         room_name: String,
+        description: Option<String>,
+        avatar_file_id: Option<Uuid>,
         admin_username: String,
         creator_username: String,
+        /// The earliest members only; page through the rest with `GetRoomMembers`
         members: Vec<MemberInfo>,
+        member_count: i64,
         created_at: DateTime<Utc>,
+        message_count: i64,
+    },
+    RoomMembers {
+        room_id: Uuid,
+        members: Vec<MemberInfo>,
+        total: i64,
     },
     RoomsInfo {
         rooms: Vec<RoomInfo>,
     },
+    RoomsSummary {
+        rooms: Vec<RoomSummary>,
+    },
     InvitationReceived {
         invitation_id: Uuid,
         room_id: Uuid,
         room_name: String,
         inviter_username: String,
+        created_at: DateTime<Utc>,
     },
     InvitationSent {
         invitation_id: Uuid,
         room_id: Uuid,
         room_name: String,
         invitee_username: String,
+        created_at: DateTime<Utc>,
     },
     InvitationDeclined {
         invitation_id: Uuid,
@@ -271,13 +679,29 @@ pub enum ServerResp {
     PendingInvitations {
         pending_invitations: Vec<InvitationInfo>,
     },
+    PendingInvitationCount {
+        count: i64,
+    },
+    RoomInvitations {
+        room_id: Uuid,
+        invitations: Vec<RoomInvitationInfo>,
+    },
+    RoomPreview {
+        invitation_id: Uuid,
+        room_id: Uuid,
+        room_name: String,
+        member_count: i64,
+        admin_username: String,
+    },
     MessageSent {
         message_id: Uuid,
         room_id: Uuid,
         room_name: String,
         content: String,
         message_type: MessageType,
+        file_id: Option<Uuid>,
         created_at: DateTime<Utc>,
+        reply_to: Option<Uuid>,
     },
     MessageReceived {
         message_id: Uuid,
@@ -286,19 +710,85 @@ pub enum ServerResp {
         author_username: Option<String>,
         content: String,
         message_type: MessageType,
+        file_id: Option<Uuid>,
         created_at: DateTime<Utc>,
+        reply_to: Option<Uuid>,
     },
     MessageEdited {
         message_id: Uuid,
         new_content: String,
+        status: MessageStatus,
+        edited_at: Option<DateTime<Utc>>,
+    },
+    /// Sent to the author instead of `MessageEdited`, like `MessageSent`
+    MessageEditConfirmed {
+        message_id: Uuid,
+        room_id: Uuid,
+        new_content: String,
+        status: MessageStatus,
+        edited_at: Option<DateTime<Utc>>,
+    },
+    UserTyping {
+        room_id: Uuid,
+        username: String,
+        is_typing: bool,
     },
     MessageDeleted {
         message_id: Uuid,
     },
+    ReactionAdded {
+        message_id: Uuid,
+        room_id: Uuid,
+        username: String,
+        emoji: String,
+    },
+    ReactionRemoved {
+        message_id: Uuid,
+        room_id: Uuid,
+        username: String,
+        emoji: String,
+    },
+    MessagePinned {
+        message_id: Uuid,
+        room_id: Uuid,
+        username: String,
+    },
+    MessageUnpinned {
+        message_id: Uuid,
+        room_id: Uuid,
+        username: String,
+    },
+    PinnedMessages {
+        room_id: Uuid,
+        messages: Vec<MessageInfo>,
+    },
+    /// Another member has read the room up to `read_at`
+    MessagesRead {
+        room_id: Uuid,
+        username: String,
+        read_at: DateTime<Utc>,
+    },
     MessageHistory {
         room_id: Uuid,
         room_name: String,
         messages: Vec<MessageInfo>,
+        /// When the member last read the room, before this fetch marked it read
+        last_read_at: Option<DateTime<Utc>>,
+        /// Older messages exist past this page
+        has_more: bool,
+        /// `before` for the next older page of `GetMessagesBefore`; `None`
+        /// once history is exhausted, and for other requests
+        next_cursor: Option<String>,
+    },
+    Thread {
+        root_message_id: Uuid,
+        room_id: Uuid,
+        messages: Vec<MessageInfo>,
+    },
+    /// A co-member uploaded a different identity key, so sessions with them
+    /// should be re-verified
+    IdentityKeyChanged {
+        username: String,
     },
     AccountDeleted {
         user_id: Uuid,
@@ -314,9 +804,45 @@ pub enum ServerResp {
         username: String,
         joined_at: DateTime<Utc>,
     },
+    /// Sent to the remaining members when someone leaves; kicks are
+    /// announced as `MemberKicked` instead
+    MemberLeft {
+        room_id: Uuid,
+        room_name: String,
+        username: String,
+    },
     UsersFound {
         users: Vec<UserInfo>,
+        /// More matches exist past this page
+        has_more: bool,
     },
+    /// Rooms ordered by their newest match, messages newest first
+    MessagesFound {
+        rooms: Vec<RoomMessageMatches>,
+    },
+    /// Newest first
+    MessageSearchResults {
+        room_id: Uuid,
+        messages: Vec<MessageInfo>,
+    },
+    DraftSaved {
+        room_id: Uuid,
+        updated_at: DateTime<Utc>,
+    },
+    /// `content` is `None` when nothing has been saved for the room
+    Draft {
+        room_id: Uuid,
+        content: Option<String>,
+        updated_at: Option<DateTime<Utc>>,
+    },
+    ConnectionInfo {
+        connection_id: Uuid,
+        connected_since: DateTime<Utc>,
+        token_expires_at: DateTime<Utc>,
+        server_version: String,
+    },
+    /// The last event before the server closes the socket to shut down
+    ServerShutdown,
     Error {
         errors: Vec<ApiErrorItem>,
     },
@@ -328,14 +854,23 @@ pub enum SystemMessageContent {
     Joined { username: String },
     Left { username: String },
     Kicked { username: String, by: String },
+    AdminTransferred { username: String, by: String },
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct UserInfo {
     pub username: String,
+    pub avatar_file_id: Option<Uuid>,
     pub created_at: DateTime<Utc>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RoomMessageMatches {
+    pub room_id: Uuid,
+    pub room_name: String,
+    pub messages: Vec<MessageInfo>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct InvitationInfo {
     pub invitation_id: Uuid,
@@ -346,12 +881,54 @@ pub struct InvitationInfo {
     pub created_at: DateTime<Utc>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JoinedRoomInfo {
+    pub invitation_id: Uuid,
+    pub room_id: Uuid,
+    pub room_name: String,
+    pub admin_username: String,
+    pub creator_username: String,
+    pub created_at: DateTime<Utc>,
+    pub joined_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct FailedInvitation {
+    pub invitation_id: Uuid,
+    pub errors: Vec<ApiErrorItem>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct FailedRoom {
+    pub room_id: Uuid,
+    pub errors: Vec<ApiErrorItem>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RoomInvitationInfo {
+    pub invitation_id: Uuid,
+    pub invitee_username: String,
+    pub inviter_username: String,
+    pub created_at: DateTime<Utc>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MemberInfo {
     pub username: String,
+    pub avatar_file_id: Option<Uuid>,
     pub joined_at: DateTime<Utc>,
 }
 
+impl From<RoomMember> for MemberInfo {
+    fn from(member: RoomMember) -> Self {
+        Self {
+            username: member.username,
+            avatar_file_id: member.avatar_file_id,
+            joined_at: member.joined_at,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MessageInfo {
     pub message_id: Uuid,
@@ -359,13 +936,66 @@ pub struct MessageInfo {
     pub content: String,
     pub message_type: MessageType,
     pub message_status: MessageStatus,
+    pub file_id: Option<Uuid>,
     pub created_at: DateTime<Utc>,
+    pub edited_at: Option<DateTime<Utc>>,
+    pub reply_to: Option<Uuid>,
+    pub reply_count: i32,
+    pub pinned: bool,
+    /// Filled in for message history; empty elsewhere
+    pub reactions: Vec<ReactionInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReactionInfo {
+    pub emoji: String,
+    pub count: i64,
+}
+
+/// Shown for user messages whose author is gone; system messages have none
+pub const DELETED_AUTHOR_PLACEHOLDER: &str = "Deleted user";
+
+impl From<UserMessage> for MessageInfo {
+    /// Deleted messages never expose their old content or attachment, even
+    /// for rows deleted before `delete_message` started blanking them
+    fn from(msg: UserMessage) -> Self {
+        let deleted = msg.status == MessageStatus::Deleted;
+        let author_username = match (msg.author_username, msg.message_type) {
+            (None, MessageType::Text | MessageType::File) => {
+                Some(DELETED_AUTHOR_PLACEHOLDER.to_string())
+            }
+            (author_username, _) => author_username,
+        };
+        Self {
+            message_id: msg.id,
+            author_username,
+            content: if deleted { String::new() } else { msg.content },
+            message_type: msg.message_type,
+            message_status: msg.status,
+            file_id: if deleted { None } else { msg.file_id },
+            created_at: msg.created_at,
+            edited_at: msg.edited_at,
+            reply_to: msg.reply_to,
+            reply_count: msg.reply_count,
+            pinned: msg.pinned,
+            reactions: Vec::new(),
+        }
+    }
+}
+
+/// `RoomInfo` without previews, for lightweight background sync
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RoomSummary {
+    pub room_id: Uuid,
+    pub unread_count: i32,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RoomInfo {
     pub room_id: Uuid,
     pub room_name: String,
+    pub description: Option<String>,
+    pub avatar_file_id: Option<Uuid>,
     pub last_message: Option<MessageInfo>,
     pub unread_count: i32,
 }
@@ -7,7 +7,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 use thiserror::Error;
 
-use crate::errors::error_codes;
+use crate::{dtos::supported_subprotocols, errors::error_codes};
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ApiErrorItem {
@@ -43,6 +43,18 @@ pub enum AppError {
     SessionExpired,
     #[error("Invalid token")]
     InvalidToken,
+    #[error("Account no longer exists")]
+    AccountNotFound,
+    #[error("Invalid reset token")]
+    InvalidResetToken,
+    #[error("Invalid verification token")]
+    InvalidVerificationToken,
+    #[error("Origin not allowed")]
+    OriginNotAllowed,
+    #[error("Unsupported WebSocket subprotocol")]
+    UnsupportedProtocol,
+    #[error("Not admin")]
+    NotAdmin,
 
     // User
     #[error("Username already exists")]
@@ -55,6 +67,8 @@ pub enum AppError {
     // Room
     #[error("Room not found")]
     RoomNotFound,
+    #[error("Invalid room name")]
+    InvalidRoomName,
     #[error("Already room member")]
     AlreadyRoomMember,
     #[error("Target already room member")]
@@ -65,6 +79,10 @@ pub enum AppError {
     TargetNotRoomMember,
     #[error("Not room admin")]
     NotRoomAdmin,
+    #[error("Room full")]
+    RoomFull,
+    #[error("Not allowed in a direct message room")]
+    DirectMessageRoom,
 
     // Invitation
     #[error("Invitation not found")]
@@ -79,10 +97,19 @@ pub enum AppError {
     MessageNotFound,
     #[error("Not message author")]
     NotMessageAuthor,
+    #[error("Message too old to delete")]
+    DeleteWindowExpired,
+    /// Items from `validate_message_content`: empty or over-long content
+    #[error("Invalid message content")]
+    InvalidMessageContent(Vec<ApiErrorItem>),
+    #[error("Invalid page, {0} messages available")]
+    InvalidPage(i64),
 
     // File
     #[error("File not found")]
     FileNotFound,
+    #[error("Not file owner")]
+    NotFileOwner,
     #[error("Exceeding file limit")]
     ExceedingFileLimit,
 
@@ -93,6 +120,15 @@ pub enum AppError {
     // General
     #[error("Invalid request format")]
     InvalidRequestFormat,
+    /// Carries the parser's explanation, e.g. which field is missing
+    #[error("Invalid request format: {0}")]
+    InvalidRequestFormatDetailed(String),
+    #[error("Unknown request type: {0}")]
+    UnknownRequestType(String),
+    #[error("Feature disabled")]
+    FeatureDisabled,
+    #[error("Rate limited")]
+    RateLimited,
 }
 
 impl AppError {
@@ -101,10 +137,24 @@ impl AppError {
             AppError::Db(_) | AppError::Internal => {
                 vec![ApiErrorItem::new(error_codes::INTERNAL_SERVER_ERROR, None)]
             }
-            AppError::Validation(errors) => errors.clone(),
+            AppError::Validation(errors) | AppError::InvalidMessageContent(errors) => {
+                errors.clone()
+            }
             AppError::InvalidRequestFormat => {
                 vec![ApiErrorItem::new(error_codes::INVALID_REQUEST_FORMAT, None)]
             }
+            AppError::InvalidRequestFormatDetailed(reason) => {
+                vec![ApiErrorItem::new(
+                    error_codes::INVALID_REQUEST_FORMAT,
+                    json!({ "reason": reason }),
+                )]
+            }
+            AppError::UnknownRequestType(request_type) => {
+                vec![ApiErrorItem::new(
+                    error_codes::UNKNOWN_REQUEST_TYPE,
+                    json!({ "type": request_type }),
+                )]
+            }
             AppError::UserHasNoKeys => {
                 vec![ApiErrorItem::new(error_codes::USER_HAS_NO_KEYS, None)]
             }
@@ -117,12 +167,37 @@ impl AppError {
             AppError::InvalidToken => {
                 vec![ApiErrorItem::new(error_codes::INVALID_TOKEN, None)]
             }
+            AppError::AccountNotFound => {
+                vec![ApiErrorItem::new(error_codes::ACCOUNT_NOT_FOUND, None)]
+            }
+            AppError::InvalidResetToken => {
+                vec![ApiErrorItem::new(error_codes::INVALID_RESET_TOKEN, None)]
+            }
+            AppError::InvalidVerificationToken => vec![ApiErrorItem::new(
+                error_codes::INVALID_VERIFICATION_TOKEN,
+                None,
+            )],
+            AppError::OriginNotAllowed => {
+                vec![ApiErrorItem::new(error_codes::ORIGIN_NOT_ALLOWED, None)]
+            }
+            AppError::UnsupportedProtocol => {
+                vec![ApiErrorItem::new(
+                    error_codes::UNSUPPORTED_PROTOCOL,
+                    json!({ "supported": supported_subprotocols() }),
+                )]
+            }
+            AppError::NotAdmin => {
+                vec![ApiErrorItem::new(error_codes::NOT_ADMIN, None)]
+            }
             AppError::UserNotFound => {
                 vec![ApiErrorItem::new(error_codes::USER_NOT_FOUND, None)]
             }
             AppError::RoomNotFound => {
                 vec![ApiErrorItem::new(error_codes::ROOM_NOT_FOUND, None)]
             }
+            AppError::InvalidRoomName => {
+                vec![ApiErrorItem::new(error_codes::INVALID_ROOM_NAME, None)]
+            }
             AppError::InvitationNotFound => {
                 vec![ApiErrorItem::new(error_codes::INVITATION_NOT_FOUND, None)]
             }
@@ -159,18 +234,42 @@ impl AppError {
             AppError::NotRoomAdmin => {
                 vec![ApiErrorItem::new(error_codes::NOT_ROOM_ADMIN, None)]
             }
+            AppError::RoomFull => {
+                vec![ApiErrorItem::new(error_codes::ROOM_FULL, None)]
+            }
+            AppError::DirectMessageRoom => {
+                vec![ApiErrorItem::new(error_codes::DIRECT_MESSAGE_ROOM, None)]
+            }
             AppError::NotMessageAuthor => {
                 vec![ApiErrorItem::new(error_codes::NOT_MESSAGE_AUTHOR, None)]
             }
+            AppError::DeleteWindowExpired => {
+                vec![ApiErrorItem::new(error_codes::DELETE_WINDOW_EXPIRED, None)]
+            }
+            AppError::InvalidPage(total) => {
+                vec![ApiErrorItem::new(
+                    error_codes::INVALID_PAGE,
+                    json!({ "total": total }),
+                )]
+            }
             AppError::FileNotFound => {
                 vec![ApiErrorItem::new(error_codes::FILE_NOT_FOUND, None)]
             }
+            AppError::NotFileOwner => {
+                vec![ApiErrorItem::new(error_codes::NOT_FILE_OWNER, None)]
+            }
             AppError::ExceedingFileLimit => {
                 vec![ApiErrorItem::new(error_codes::FILE_LIMIT_EXCEEDED, None)]
             }
             AppError::KeyBackupNotFound => {
                 vec![ApiErrorItem::new(error_codes::KEY_BACKUP_NOT_FOUND, None)]
             }
+            AppError::FeatureDisabled => {
+                vec![ApiErrorItem::new(error_codes::FEATURE_DISABLED, None)]
+            }
+            AppError::RateLimited => {
+                vec![ApiErrorItem::new(error_codes::RATE_LIMITED, None)]
+            }
         }
     }
 }
@@ -197,10 +296,34 @@ impl IntoResponse for AppError {
                 tracing::debug!("Invalid request format");
                 (StatusCode::BAD_REQUEST, self.to_api_errors())
             }
+            AppError::InvalidRequestFormatDetailed(reason) => {
+                tracing::debug!("Invalid request format: {}", reason);
+                (StatusCode::BAD_REQUEST, self.to_api_errors())
+            }
+            AppError::UnknownRequestType(request_type) => {
+                tracing::debug!("Unknown request type: {}", request_type);
+                (StatusCode::BAD_REQUEST, self.to_api_errors())
+            }
             AppError::UserHasNoKeys => {
                 tracing::debug!("User has no keys");
                 (StatusCode::BAD_REQUEST, self.to_api_errors())
             }
+            AppError::InvalidResetToken => {
+                tracing::debug!("Invalid reset token");
+                (StatusCode::BAD_REQUEST, self.to_api_errors())
+            }
+            AppError::InvalidVerificationToken => {
+                tracing::debug!("Invalid verification token");
+                (StatusCode::BAD_REQUEST, self.to_api_errors())
+            }
+            AppError::UnsupportedProtocol => {
+                tracing::debug!("Unsupported WebSocket subprotocol");
+                (StatusCode::BAD_REQUEST, self.to_api_errors())
+            }
+            AppError::InvalidMessageContent(errors) => {
+                tracing::debug!("Invalid message content: {:?}", errors);
+                (StatusCode::BAD_REQUEST, self.to_api_errors())
+            }
 
             // 401
             AppError::WrongCredentials => {
@@ -215,6 +338,10 @@ impl IntoResponse for AppError {
                 tracing::warn!("Invalid token");
                 (StatusCode::UNAUTHORIZED, self.to_api_errors())
             }
+            AppError::AccountNotFound => {
+                tracing::warn!("Token for an account that no longer exists");
+                (StatusCode::UNAUTHORIZED, self.to_api_errors())
+            }
 
             // 404
             AppError::UserNotFound => {
@@ -243,6 +370,10 @@ impl IntoResponse for AppError {
                 tracing::debug!("Username already exists");
                 (StatusCode::CONFLICT, self.to_api_errors())
             }
+            AppError::InvalidRoomName => {
+                tracing::debug!("Invalid room name");
+                (StatusCode::BAD_REQUEST, self.to_api_errors())
+            }
             AppError::AlreadyRoomMember => {
                 tracing::debug!("Already room member");
                 (StatusCode::CONFLICT, self.to_api_errors())
@@ -269,14 +400,42 @@ impl IntoResponse for AppError {
                 tracing::warn!("Not room admin");
                 (StatusCode::FORBIDDEN, self.to_api_errors())
             }
+            AppError::RoomFull => {
+                tracing::debug!("Room full");
+                (StatusCode::CONFLICT, self.to_api_errors())
+            }
+            AppError::DirectMessageRoom => {
+                tracing::debug!("Direct message room");
+                (StatusCode::FORBIDDEN, self.to_api_errors())
+            }
             AppError::NotMessageAuthor => {
                 tracing::warn!("Not message author");
                 (StatusCode::FORBIDDEN, self.to_api_errors())
             }
+            AppError::DeleteWindowExpired => {
+                tracing::debug!("Message too old to delete");
+                (StatusCode::FORBIDDEN, self.to_api_errors())
+            }
+            AppError::InvalidPage(total) => {
+                tracing::debug!("Invalid page, {} messages available", total);
+                (StatusCode::BAD_REQUEST, self.to_api_errors())
+            }
+            AppError::OriginNotAllowed => {
+                tracing::warn!("Origin not allowed");
+                (StatusCode::FORBIDDEN, self.to_api_errors())
+            }
+            AppError::NotAdmin => {
+                tracing::warn!("Not admin");
+                (StatusCode::FORBIDDEN, self.to_api_errors())
+            }
             AppError::FileNotFound => {
                 tracing::debug!("File not found");
                 (StatusCode::NOT_FOUND, self.to_api_errors())
             }
+            AppError::NotFileOwner => {
+                tracing::warn!("Not file owner");
+                (StatusCode::FORBIDDEN, self.to_api_errors())
+            }
             AppError::ExceedingFileLimit => {
                 tracing::debug!("Exceeding file limit");
                 (StatusCode::BAD_REQUEST, self.to_api_errors())
@@ -285,6 +444,16 @@ impl IntoResponse for AppError {
                 tracing::debug!("Key backup not found");
                 (StatusCode::NOT_FOUND, self.to_api_errors())
             }
+            AppError::FeatureDisabled => {
+                tracing::debug!("Feature disabled");
+                (StatusCode::FORBIDDEN, self.to_api_errors())
+            }
+
+            // 429
+            AppError::RateLimited => {
+                tracing::warn!("Rate limited");
+                (StatusCode::TOO_MANY_REQUESTS, self.to_api_errors())
+            }
         };
 
         (status, Json(json!({ "errors": errors }))).into_response()
@@ -11,23 +11,42 @@ pub const CONFIRM_PASSWORD_REQUIRED: &str = "confirm_password_required";
 pub const PASSWORD_CONFLICT: &str = "password_conflict";
 pub const SESSION_EXPIRED: &str = "session_expired";
 pub const INVALID_TOKEN: &str = "invalid_token";
+pub const ACCOUNT_NOT_FOUND: &str = "account_not_found";
+pub const INVALID_RESET_TOKEN: &str = "invalid_reset_token";
+pub const INVALID_VERIFICATION_TOKEN: &str = "invalid_verification_token";
+pub const ORIGIN_NOT_ALLOWED: &str = "origin_not_allowed";
+pub const UNSUPPORTED_PROTOCOL: &str = "unsupported_protocol";
+pub const NOT_ADMIN: &str = "not_admin";
+pub const RECOVERY_EMAIL_INVALID: &str = "recovery_email_invalid";
 pub const INTERNAL_SERVER_ERROR: &str = "internal_server_error";
 pub const INVALID_REQUEST_FORMAT: &str = "invalid_request_format";
+pub const UNKNOWN_REQUEST_TYPE: &str = "unknown_request_type";
 pub const FILE_LIMIT_EXCEEDED: &str = "file_limit_exceeded";
 pub const FILE_NOT_FOUND: &str = "file_not_found";
+pub const NOT_FILE_OWNER: &str = "not_file_owner";
 pub const KEY_BACKUP_NOT_FOUND: &str = "key_backup_not_found";
+pub const FEATURE_DISABLED: &str = "feature_disabled";
+pub const RATE_LIMITED: &str = "rate_limited";
 
 // WebSocket specific errors
 pub const ROOM_NOT_FOUND: &str = "room_not_found";
+pub const INVALID_ROOM_NAME: &str = "invalid_room_name";
 pub const ALREADY_ROOM_MEMBER: &str = "already_room_member";
 pub const TARGET_ALREADY_ROOM_MEMBER: &str = "target_already_room_member";
 pub const NOT_ROOM_MEMBER: &str = "not_room_member";
 pub const TARGET_NOT_ROOM_MEMBER: &str = "target_not_room_member";
 pub const NOT_ROOM_ADMIN: &str = "not_room_admin";
+pub const ROOM_FULL: &str = "room_full";
+pub const DIRECT_MESSAGE_ROOM: &str = "direct_message_room";
+pub const ROOM_DESCRIPTION_TOO_LONG: &str = "room_description_too_long";
 pub const USER_NOT_FOUND: &str = "user_not_found";
 pub const INVITATION_NOT_FOUND: &str = "invitation_not_found";
 pub const NO_PENDING_INVITATION: &str = "no_pending_invitation";
 pub const ALREADY_INVITED: &str = "already_invited";
 pub const MESSAGE_NOT_FOUND: &str = "message_not_found";
 pub const NOT_MESSAGE_AUTHOR: &str = "not_message_author";
+pub const MESSAGE_CONTENT_REQUIRED: &str = "message_content_required";
+pub const MESSAGE_TOO_LONG: &str = "message_too_long";
+pub const DELETE_WINDOW_EXPIRED: &str = "delete_window_expired";
+pub const INVALID_PAGE: &str = "invalid_page";
 pub const USER_HAS_NO_KEYS: &str = "user_has_no_keys";
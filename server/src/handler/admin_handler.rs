@@ -0,0 +1,103 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use tracing::{info, instrument, warn};
+
+use crate::{
+    config::AppState,
+    database::{room_members::RoomMemberRepository, users::UserRepository},
+    dtos::{
+        AdminDisconnectReqDto, AdminDisconnectRespDto, AdminUserRoomDto, AdminUserSummaryRespDto,
+        GrantRoleReqDto, GrantRoleRespDto,
+    },
+    errors::error::AppError,
+    utils::middleware::AuthUser,
+};
+
+use super::ws_handler::utils::{CLOSE_POLICY, close_connection};
+
+#[instrument(skip(state))]
+pub async fn disconnect_user(
+    user: AuthUser,
+    State(state): State<AppState>,
+    Json(body): Json<AdminDisconnectReqDto>,
+) -> Result<Json<AdminDisconnectRespDto>, AppError> {
+    user.require_admin()?;
+    info!("Admin {} disconnecting user {}", user.user_id, body.user_id);
+
+    let disconnected =
+        close_connection(&state, body.user_id, CLOSE_POLICY, "disconnected_by_admin");
+
+    Ok(Json(AdminDisconnectRespDto { disconnected }))
+}
+
+#[instrument(skip(state))]
+pub async fn grant_role(
+    user: AuthUser,
+    State(state): State<AppState>,
+    Json(body): Json<GrantRoleReqDto>,
+) -> Result<Json<GrantRoleRespDto>, AppError> {
+    user.require_admin()?;
+    info!(
+        "Admin {} granting {:?} to {}",
+        user.user_id, body.role, body.username
+    );
+
+    // Takes effect once the user's current access token is replaced
+    match state.db.update_role(&body.username, body.role).await? {
+        Some(updated) => Ok(Json(GrantRoleRespDto {
+            username: updated.username,
+            role: updated.role,
+        })),
+        None => {
+            warn!("User not found: {}", body.username);
+            Err(AppError::UserNotFound)
+        }
+    }
+}
+
+#[instrument(skip(state))]
+pub async fn get_user_summary(
+    user: AuthUser,
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+) -> Result<Json<AdminUserSummaryRespDto>, AppError> {
+    user.require_admin()?;
+    info!("Admin {} inspecting user {}", user.user_id, username);
+
+    let target = match state.db.get_user_by_username(&username).await? {
+        Some(target) => target,
+        None => {
+            warn!("User not found: {}", username);
+            return Err(AppError::UserNotFound);
+        }
+    };
+
+    let rooms = state
+        .db
+        .get_current_memberships(target.id)
+        .await?
+        .into_iter()
+        .map(|m| AdminUserRoomDto {
+            room_id: m.room_id,
+            room_name: m.room_name,
+            joined_at: m.joined_at,
+        })
+        .collect();
+    let connections = state
+        .channels
+        .get(&target.id)
+        .map_or(0, |connections| connections.len());
+
+    Ok(Json(AdminUserSummaryRespDto {
+        id: target.id,
+        username: target.username,
+        role: target.role,
+        created_at: target.created_at,
+        avatar_file_id: target.avatar_file_id,
+        has_recovery_email: target.recovery_email.is_some(),
+        connections,
+        rooms,
+    }))
+}
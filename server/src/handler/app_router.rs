@@ -1,30 +1,52 @@
 use axum::{
     Router,
-    routing::{get, post},
+    routing::{delete, get, post},
 };
 
 use crate::config::AppState;
 
 use super::{
-    auth_handler::{login, refresh_token, register},
-    file_handler::{get_file, upload_file},
-    keys_handler::{get_key_count, get_prekey_bundle, upload_keys},
+    admin_handler::{disconnect_user, get_user_summary, grant_role},
+    auth_handler::{
+        confirm_password_reset, login, logout, logout_all, refresh_token, register,
+        request_password_reset, set_avatar, set_recovery_email, verify_recovery_email,
+    },
+    features_handler::get_features,
+    file_handler::{delete_orphan_files, get_file, upload_file},
+    keys_handler::{get_key_count, get_prekey_bundle, get_room_prekey_bundles, upload_keys},
+    metrics_handler::get_metrics,
+    version_handler::get_version,
     ws_handler::ws_router::ws_handler,
 };
 
 pub fn handler(state: AppState) -> Router {
     let api = Router::new()
+        .route("/features", get(get_features))
+        .route("/version", get(get_version))
         .route("/register", post(register))
         .route("/login", post(login))
         .route("/refresh-token", post(refresh_token))
+        .route("/logout", post(logout))
+        .route("/logout-all", post(logout_all))
+        .route("/recovery-email", post(set_recovery_email))
+        .route("/recovery-email/verify", post(verify_recovery_email))
+        .route("/avatar", post(set_avatar))
+        .route("/password-reset", post(request_password_reset))
+        .route("/password-reset/confirm", post(confirm_password_reset))
         .route("/keys", post(upload_keys))
         .route("/keys/status/count", get(get_key_count))
         .route("/keys/{username}", get(get_prekey_bundle))
+        .route("/keys/room/{room_id}", get(get_room_prekey_bundles))
         .route("/files", post(upload_file))
-        .route("/files/download", post(get_file));
+        .route("/files/download", post(get_file))
+        .route("/files/orphans", delete(delete_orphan_files))
+        .route("/admin/disconnect", post(disconnect_user))
+        .route("/admin/grant", post(grant_role))
+        .route("/admin/users/{username}", get(get_user_summary));
 
     Router::new()
         .nest("/api", api)
         .route("/ws_handler", get(ws_handler))
+        .route("/metrics", get(get_metrics))
         .with_state(state)
 }
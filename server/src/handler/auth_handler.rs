@@ -1,18 +1,28 @@
-use axum::{Json, extract::State};
+use axum::{Json, extract::State, http::StatusCode};
 use chrono::{Duration, Utc};
-use tracing::{info, instrument};
+use tracing::{error, info, instrument, warn};
+use uuid::Uuid;
 
 use crate::{
     config::AppState,
-    database::{models::UserRole, refresh_token::RefreshTokenRepository, users::UserRepository},
+    database::{
+        files::FileRepository, models::UserRole, password_resets::PasswordResetRepository,
+        refresh_token::RefreshTokenRepository, users::UserRepository,
+    },
     dtos::{
-        LoginReqDto, LoginRespDto, RefreshTokenReqDto, RefreshTokenRespDto, RegisterReqDto,
-        RegisterRespDto,
+        AvatarRespDto, LoginReqDto, LoginRespDto, LogoutAllRespDto, PasswordResetConfirmReqDto,
+        PasswordResetReqDto, RecoveryEmailRespDto, RefreshTokenReqDto, RefreshTokenRespDto,
+        RegisterReqDto, RegisterRespDto, SetAvatarReqDto, SetRecoveryEmailReqDto,
+        VerifyRecoveryEmailReqDto,
     },
     errors::error::AppError,
     utils::{
         hash::{hash_data, hash_password, verify_hashed_password},
-        token::{generate_access_token, generate_refresh_token},
+        middleware::AuthUser,
+        token::{
+            generate_access_token, generate_email_verification_token,
+            generate_password_reset_token, generate_refresh_token,
+        },
     },
 };
 
@@ -24,11 +34,17 @@ pub async fn register(
     info!("Registering new user");
     body.validate().map_err(AppError::Validation)?;
 
-    let password_hash = hash_password(body.password)?;
+    let password_hash = hash_password(body.password, state.config.scrypt_params()?)?;
+    let role = if is_bootstrap_admin(&state, &body.username) {
+        info!("Registering bootstrap admin {}", body.username);
+        UserRole::Admin
+    } else {
+        UserRole::User
+    };
 
     match state
         .db
-        .insert_user(&body.username, &password_hash, UserRole::User)
+        .insert_user(&body.username, &password_hash, role)
         .await?
     {
         Some(user) => {
@@ -51,7 +67,7 @@ pub async fn login(
 ) -> Result<Json<LoginRespDto>, AppError> {
     info!("User logging in");
     body.validate().map_err(AppError::Validation)?;
-    let user = match state.db.get_user_by_username(&body.username).await? {
+    let mut user = match state.db.get_user_by_username(&body.username).await? {
         Some(user) => user,
         None => return Err(AppError::WrongCredentials),
     };
@@ -60,9 +76,21 @@ pub async fn login(
         return Err(AppError::WrongCredentials);
     }
 
+    // Promotes a bootstrap admin who registered before the setting was made
+    if user.role != UserRole::Admin && is_bootstrap_admin(&state, &user.username) {
+        info!("Granting admin to bootstrap admin {}", user.username);
+        if let Some(promoted) = state
+            .db
+            .update_role(&user.username, UserRole::Admin)
+            .await?
+        {
+            user = promoted;
+        }
+    }
+
     let access_token = generate_access_token(
         user.id,
-        UserRole::User,
+        user.role,
         state.config.jwt_secret.as_bytes(),
         state.config.access_expiry,
     )?;
@@ -85,6 +113,10 @@ pub async fn login(
     Ok(Json::<LoginRespDto>(login_response))
 }
 
+fn is_bootstrap_admin(state: &AppState, username: &str) -> bool {
+    state.config.bootstrap_admin_username.as_deref() == Some(username)
+}
+
 #[instrument(skip(state, body))]
 pub async fn refresh_token(
     State(state): State<AppState>,
@@ -100,38 +132,283 @@ pub async fn refresh_token(
         Some(token) => token,
         None => return Err(AppError::SessionExpired),
     };
+    if refresh_token.used_at.is_some() {
+        return Err(revoke_after_reuse(&state, refresh_token.user_id).await);
+    }
     if refresh_token.expires_at < Utc::now() {
         return Err(AppError::SessionExpired);
     }
 
-    let user_id = refresh_token.user_id;
-
-    let _ = state
-        .db
-        .delete_refresh_token_by_hash(&refresh_token_hash)
-        .await?;
+    let user = match state.db.get_user_by_id(refresh_token.user_id).await? {
+        Some(user) => user,
+        None => return Err(AppError::SessionExpired),
+    };
+    let user_id = user.id;
 
     let access_token = generate_access_token(
         user_id,
-        UserRole::User,
+        user.role,
         state.config.jwt_secret.as_bytes(),
         state.config.access_expiry,
     )?;
-    let refresh_token = generate_refresh_token()?;
-    let refresh_token_hash = hash_data(&refresh_token.as_bytes());
+    let new_refresh_token = generate_refresh_token()?;
+    let new_refresh_token_hash = hash_data(&new_refresh_token.as_bytes());
 
-    let _ = state
+    let replacement = state
         .db
         .insert_refresh_token_by_hash(
             user_id,
-            &refresh_token_hash,
+            &new_refresh_token_hash,
             Duration::seconds(state.config.refresh_expiry),
         )
         .await?;
 
+    // Losing this race means the same token was rotated twice concurrently
+    if state
+        .db
+        .mark_refresh_token_used(&refresh_token_hash, replacement.id)
+        .await?
+        .is_none()
+    {
+        return Err(revoke_after_reuse(&state, user_id).await);
+    }
+
     let refresh_response = RefreshTokenRespDto {
         access_token,
-        refresh_token,
+        refresh_token: new_refresh_token,
     };
     Ok(Json::<RefreshTokenRespDto>(refresh_response))
 }
+
+#[instrument(skip(state, body))]
+pub async fn logout(
+    State(state): State<AppState>,
+    Json(body): Json<RefreshTokenReqDto>,
+) -> Result<StatusCode, AppError> {
+    info!("Logging out");
+    let refresh_token_hash = hash_data(body.refresh_token.as_bytes());
+    match state
+        .db
+        .delete_refresh_token_by_hash(&refresh_token_hash)
+        .await?
+    {
+        Some(token) => {
+            info!("User {} logged out", token.user_id);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        None => Err(AppError::SessionExpired),
+    }
+}
+
+/// Access tokens already handed out stay valid until they expire; nothing
+/// can be refreshed afterwards
+#[instrument(skip(state))]
+pub async fn logout_all(
+    user: AuthUser,
+    State(state): State<AppState>,
+) -> Result<Json<LogoutAllRespDto>, AppError> {
+    info!("Logging out every session of user {}", user.user_id);
+    let revoked = state.db.revoke_tokens_for_user(user.user_id).await?;
+    info!(
+        "Revoked {} refresh tokens of user {}",
+        revoked, user.user_id
+    );
+
+    Ok(Json(LogoutAllRespDto { revoked }))
+}
+
+/// A rotated token coming back means it leaked, and the legitimate holder
+/// can't be told apart from whoever else has it, so every session ends
+async fn revoke_after_reuse(state: &AppState, user_id: Uuid) -> AppError {
+    warn!(
+        "Refresh token reuse detected for user {}, revoking all sessions",
+        user_id
+    );
+    match state.db.revoke_tokens_for_user(user_id).await {
+        Ok(_) => AppError::SessionExpired,
+        Err(e) => AppError::from(e),
+    }
+}
+
+#[instrument(skip(state, body))]
+pub async fn set_recovery_email(
+    user: AuthUser,
+    State(state): State<AppState>,
+    Json(body): Json<SetRecoveryEmailReqDto>,
+) -> Result<Json<RecoveryEmailRespDto>, AppError> {
+    info!("Setting recovery email for user {}", user.user_id);
+    body.validate().map_err(AppError::Validation)?;
+
+    let recovery_email = body.recovery_email.as_deref().map(str::trim);
+
+    let user = match state
+        .db
+        .update_recovery_email(user.user_id, recovery_email)
+        .await?
+    {
+        Some(user) => user,
+        None => return Err(AppError::UserNotFound),
+    };
+
+    // A new address has to prove it can receive mail before resets go to it
+    if let Some(email) = &user.recovery_email {
+        let token = generate_email_verification_token()?;
+        let token_hash = hash_data(token.as_bytes());
+
+        state
+            .db
+            .insert_recovery_email_token(
+                user.id,
+                &token_hash,
+                Duration::seconds(state.config.password_reset_expiry),
+            )
+            .await?;
+
+        if let Err(e) = state
+            .notifier
+            .send_recovery_email_verification(user.id, email, &token)
+            .await
+        {
+            error!(
+                "Failed to send recovery email verification to user {}: {:?}",
+                user.id, e
+            );
+        }
+    }
+
+    Ok(Json(RecoveryEmailRespDto {
+        recovery_email: user.recovery_email,
+        recovery_email_verified: false,
+    }))
+}
+
+#[instrument(skip(state, body))]
+pub async fn verify_recovery_email(
+    user: AuthUser,
+    State(state): State<AppState>,
+    Json(body): Json<VerifyRecoveryEmailReqDto>,
+) -> Result<Json<RecoveryEmailRespDto>, AppError> {
+    info!("Verifying recovery email for user {}", user.user_id);
+    let token_hash = hash_data(body.token.as_bytes());
+
+    match state
+        .db
+        .verify_recovery_email(user.user_id, &token_hash)
+        .await?
+    {
+        Some(user) => Ok(Json(RecoveryEmailRespDto {
+            recovery_email: user.recovery_email,
+            recovery_email_verified: user.recovery_email_verified_at.is_some(),
+        })),
+        None => Err(AppError::InvalidVerificationToken),
+    }
+}
+
+#[instrument(skip(state, body))]
+pub async fn set_avatar(
+    user: AuthUser,
+    State(state): State<AppState>,
+    Json(body): Json<SetAvatarReqDto>,
+) -> Result<Json<AvatarRespDto>, AppError> {
+    info!("Setting avatar for user {}", user.user_id);
+
+    if let Some(file_id) = body.file_id {
+        let file = match state.db.get_file(file_id).await? {
+            Some(file) => file,
+            None => return Err(AppError::FileNotFound),
+        };
+
+        if file.uploader_id != Some(user.user_id) {
+            return Err(AppError::NotFileOwner);
+        }
+    }
+
+    match state.db.update_avatar(user.user_id, body.file_id).await? {
+        Some(user) => Ok(Json(AvatarRespDto {
+            avatar_file_id: user.avatar_file_id,
+        })),
+        None => Err(AppError::UserNotFound),
+    }
+}
+
+#[instrument(skip(state, body), fields(username = %body.username))]
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    Json(body): Json<PasswordResetReqDto>,
+) -> Result<StatusCode, AppError> {
+    info!("Password reset requested");
+    if !state.config.password_reset_enabled {
+        return Err(AppError::FeatureDisabled);
+    }
+
+    // Always answer the same way so the endpoint can't be used to probe accounts
+    let user = match state.db.get_user_by_username(&body.username).await? {
+        Some(user) => user,
+        None => return Ok(StatusCode::ACCEPTED),
+    };
+
+    let email = match user.recovery_email {
+        Some(email) => email,
+        None => {
+            warn!("User {} has no recovery email", user.id);
+            return Ok(StatusCode::ACCEPTED);
+        }
+    };
+
+    if user.recovery_email_verified_at.is_none() {
+        warn!("User {} has not verified their recovery email", user.id);
+        return Ok(StatusCode::ACCEPTED);
+    }
+
+    let token = generate_password_reset_token()?;
+    let token_hash = hash_data(token.as_bytes());
+
+    let _ = state
+        .db
+        .insert_password_reset_token(
+            user.id,
+            &token_hash,
+            Duration::seconds(state.config.password_reset_expiry),
+        )
+        .await?;
+
+    if let Err(e) = state
+        .notifier
+        .send_password_reset(user.id, &email, &token)
+        .await
+    {
+        error!(
+            "Failed to notify user {} of password reset: {:?}",
+            user.id, e
+        );
+    }
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[instrument(skip(state, body))]
+pub async fn confirm_password_reset(
+    State(state): State<AppState>,
+    Json(body): Json<PasswordResetConfirmReqDto>,
+) -> Result<StatusCode, AppError> {
+    info!("Confirming password reset");
+    if !state.config.password_reset_enabled {
+        return Err(AppError::FeatureDisabled);
+    }
+    body.validate().map_err(AppError::Validation)?;
+
+    let password_hash = hash_password(body.password, state.config.scrypt_params()?)?;
+    let token_hash = hash_data(body.token.as_bytes());
+
+    match state
+        .db
+        .consume_reset_token_and_update_password(&token_hash, &password_hash)
+        .await?
+    {
+        Some(user) => {
+            info!("Password reset completed for user {}", user.id);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        None => Err(AppError::InvalidResetToken),
+    }
+}
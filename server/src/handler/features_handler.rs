@@ -0,0 +1,18 @@
+use axum::{Json, extract::State};
+use tracing::{info, instrument};
+
+use crate::{config::AppState, dtos::FeaturesRespDto};
+
+#[instrument(skip(state))]
+pub async fn get_features(State(state): State<AppState>) -> Json<FeaturesRespDto> {
+    info!("Getting enabled features");
+    Json(FeaturesRespDto {
+        password_reset: state.config.password_reset_enabled,
+        file_upload: state.config.file_upload_enabled,
+        // Always on, but older servers don't have them
+        reactions: true,
+        drafts: true,
+        pins: true,
+        direct_messages: true,
+    })
+}
@@ -2,6 +2,7 @@ use axum::{
     Json,
     extract::{Multipart, State},
 };
+use chrono::{Duration, Utc};
 use tracing::{info, instrument};
 
 use crate::{
@@ -9,7 +10,7 @@ use crate::{
     database::{
         files::FileRepository, room_members::RoomMemberRepository, user_messages::MessageRepository,
     },
-    dtos::{GetFileReqDto, GetFileRespDto, UploadFileRespDto},
+    dtos::{DeleteOrphanFilesRespDto, GetFileReqDto, GetFileRespDto, UploadFileRespDto},
     errors::error::AppError,
     utils::{hash::hash_data, middleware::AuthUser},
 };
@@ -18,11 +19,14 @@ const MAX_FILE_SIZE: usize = 50 * 1024 * 1024; // 50 MB
 
 #[instrument(skip(state, body))]
 pub async fn upload_file(
-    _user: AuthUser,
+    user: AuthUser,
     State(state): State<AppState>,
     mut body: Multipart,
 ) -> Result<Json<UploadFileRespDto>, AppError> {
     info!("Uploading file");
+    if !state.config.file_upload_enabled {
+        return Err(AppError::FeatureDisabled);
+    }
 
     let mut encrypted_data: Option<Vec<u8>> = None;
     let mut encrypted_metadata: Option<Vec<u8>> = None;
@@ -62,7 +66,13 @@ pub async fn upload_file(
 
     let file = state
         .db
-        .insert_file(encrypted_data, encrypted_metadata, size_in_bytes, file_hash)
+        .insert_file(
+            encrypted_data,
+            encrypted_metadata,
+            size_in_bytes,
+            file_hash,
+            user.user_id,
+        )
         .await?;
 
     Ok(Json(UploadFileRespDto {
@@ -103,3 +113,20 @@ pub async fn get_file(
         uploaded_at: file.uploaded_at,
     }))
 }
+
+/// Removes uploads that were never attached to anything, e.g. when a client
+/// crashed between uploading and sending
+#[instrument(skip(state))]
+pub async fn delete_orphan_files(
+    user: AuthUser,
+    State(state): State<AppState>,
+) -> Result<Json<DeleteOrphanFilesRespDto>, AppError> {
+    info!("Deleting orphan files");
+    user.require_admin()?;
+
+    let uploaded_before = Utc::now() - Duration::seconds(state.config.orphan_file_grace_period);
+    let deleted = state.db.delete_orphan_files(uploaded_before).await?;
+    info!("Deleted {} orphan files", deleted);
+
+    Ok(Json(DeleteOrphanFilesRespDto { deleted }))
+}
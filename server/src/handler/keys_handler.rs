@@ -1,31 +1,57 @@
+use std::collections::HashSet;
+
 use axum::{
     Json,
     extract::{Path, State},
 };
-use tracing::{info, instrument, warn};
+use tracing::{error, info, instrument, warn};
+use uuid::Uuid;
 
 use crate::{
     config::AppState,
-    database::{keys::KeyRepository, users::UserRepository},
+    database::{
+        keys::KeyRepository, room_members::RoomMemberRepository, rooms::RoomRepository,
+        users::UserRepository,
+    },
     dtos::{
-        KeyCountRespDto, OneTimePreKeyDto, PreKeyBundleRespDto, SignedPreKeyDto, UploadKeysReqDto,
+        KeyCountRespDto, OneTimePreKeyDto, PreKeyBundleRespDto, RoomPreKeyBundlesRespDto,
+        ServerResp, SignedPreKeyDto, UploadKeysReqDto, UploadKeysRespDto, UserPreKeyBundleDto,
     },
     errors::error::AppError,
     utils::middleware::AuthUser,
 };
 
+use super::ws_handler::utils::send_event;
+
 #[instrument(skip(state, body))]
 pub async fn upload_keys(
     user: AuthUser,
     State(state): State<AppState>,
     Json(body): Json<UploadKeysReqDto>,
-) -> Result<(), AppError> {
+) -> Result<Json<UploadKeysRespDto>, AppError> {
     info!("Uploading keys for user {}", user.user_id);
 
-    let _ = state
+    // The database would silently keep only one key per id
+    let mut key_ids = HashSet::new();
+    if !body
+        .one_time_prekeys
+        .iter()
+        .all(|k| key_ids.insert(k.key_id))
+    {
+        warn!(
+            "Rejecting key upload for user {} with duplicate one-time prekey ids",
+            user.user_id
+        );
+        return Err(AppError::InvalidRequestFormat);
+    }
+
+    let previous = state.db.get_identity_key(user.user_id).await?;
+    let identity_key = state
         .db
         .upsert_identity_key(user.user_id, body.identity_key, body.registration_id)
         .await?;
+    let key_changed =
+        previous.is_some_and(|previous| previous.identity_key != identity_key.identity_key);
 
     let _ = state
         .db
@@ -43,12 +69,69 @@ pub async fn upload_keys(
         .map(|k| (k.key_id, k.public_key))
         .collect();
 
-    state
+    let accepted = state
         .db
         .upload_one_time_prekeys(user.user_id, ot_keys)
         .await?;
 
-    Ok(())
+    if key_changed {
+        notify_identity_key_changed(&state, user.user_id).await;
+    }
+
+    Ok(Json(UploadKeysRespDto {
+        accepted_one_time_keys: accepted as usize,
+    }))
+}
+
+/// Sessions live on the clients, so the server can't invalidate them itself;
+/// it tells everyone sharing a room with the user instead. The new keys are
+/// already stored, so failures here are only logged.
+async fn notify_identity_key_changed(state: &AppState, user_id: Uuid) {
+    let username = match state.db.get_user_by_id(user_id).await {
+        Ok(Some(user)) => user.username,
+        Ok(None) => return,
+        Err(e) => {
+            error!("Database error getting user by id {}: {:?}", user_id, e);
+            return;
+        }
+    };
+
+    let memberships = match state.db.get_current_memberships(user_id).await {
+        Ok(memberships) => memberships,
+        Err(e) => {
+            error!(
+                "Database error getting memberships of user {}: {:?}",
+                user_id, e
+            );
+            return;
+        }
+    };
+
+    let mut peers = HashSet::new();
+    for membership in memberships {
+        match state.db.get_members(membership.room_id).await {
+            Ok(members) => peers.extend(
+                members
+                    .into_iter()
+                    .map(|m| m.user_id)
+                    .filter(|id| *id != user_id),
+            ),
+            Err(e) => error!(
+                "Database error getting members for room {}: {:?}",
+                membership.room_id, e
+            ),
+        }
+    }
+
+    info!(
+        "Identity key of user {} changed, notifying {} peers",
+        user_id,
+        peers.len()
+    );
+    let event = ServerResp::IdentityKeyChanged { username };
+    for peer in peers {
+        send_event(state, peer, event.clone());
+    }
 }
 
 #[instrument(skip(state))]
@@ -60,7 +143,10 @@ pub async fn get_key_count(
 
     let count = state.db.get_prekey_bundle_counts(user.user_id).await?;
 
-    Ok(Json(KeyCountRespDto { count }))
+    Ok(Json(KeyCountRespDto {
+        count,
+        needs_replenishment: count < state.config.prekey_low_watermark,
+    }))
 }
 
 #[instrument(skip(state))]
@@ -113,3 +199,78 @@ pub async fn get_prekey_bundle(
         }),
     }))
 }
+
+/// Upper bound on one-time prekeys a single batch request may consume.
+///
+/// One-time prekeys give the first message of a session forward secrecy even
+/// if the signed prekey later leaks. Bundles past the cap are served without
+/// one, which still works (X3DH falls back to the signed prekey) but trades
+/// that extra protection for not letting one request drain a busy room.
+const MAX_BATCH_ONE_TIME_PREKEYS: usize = 100;
+
+#[instrument(skip(state))]
+pub async fn get_room_prekey_bundles(
+    user: AuthUser,
+    State(state): State<AppState>,
+    Path(room_id): Path<Uuid>,
+) -> Result<Json<RoomPreKeyBundlesRespDto>, AppError> {
+    info!("Getting prekey bundles for room {}", room_id);
+
+    if state.db.get_room_by_id(room_id).await?.is_none() {
+        warn!("Room not found: {}", room_id);
+        return Err(AppError::RoomNotFound);
+    }
+    if !state.db.is_member(room_id, user.user_id).await? {
+        warn!("User {} is not a member of room {}", user.user_id, room_id);
+        return Err(AppError::NotRoomMember);
+    }
+
+    let mut seen = HashSet::from([user.user_id]);
+    let mut consumed = 0;
+    let mut bundles = Vec::new();
+    for member in state.db.get_members(room_id).await? {
+        // At most one one-time prekey per user per request
+        if !seen.insert(member.user_id) {
+            continue;
+        }
+
+        let (identity_key, signed_prekey) = match (
+            state.db.get_identity_key(member.user_id).await?,
+            state.db.get_signed_prekey(member.user_id).await?,
+        ) {
+            (Some(identity_key), Some(signed_prekey)) => (identity_key, signed_prekey),
+            _ => {
+                warn!("User {} has no keys, skipping", member.user_id);
+                continue;
+            }
+        };
+
+        let one_time_prekey = if consumed < MAX_BATCH_ONE_TIME_PREKEYS {
+            state.db.consume_one_time_prekey(member.user_id).await?
+        } else {
+            None
+        };
+        if one_time_prekey.is_some() {
+            consumed += 1;
+        }
+
+        bundles.push(UserPreKeyBundleDto {
+            username: member.username,
+            bundle: PreKeyBundleRespDto {
+                identity_key: identity_key.identity_key,
+                registration_id: identity_key.registration_id,
+                signed_prekey: SignedPreKeyDto {
+                    key_id: signed_prekey.key_id,
+                    public_key: signed_prekey.public_key,
+                    signature: signed_prekey.signature,
+                },
+                one_time_prekey: one_time_prekey.map(|k| OneTimePreKeyDto {
+                    key_id: k.key_id,
+                    public_key: k.public_key,
+                }),
+            },
+        });
+    }
+
+    Ok(Json(RoomPreKeyBundlesRespDto { bundles }))
+}
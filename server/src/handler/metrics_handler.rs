@@ -0,0 +1,18 @@
+use axum::{extract::State, http::header, response::IntoResponse};
+use tracing::instrument;
+
+use crate::{config::AppState, errors::error::AppError, utils::middleware::AuthUser};
+
+/// Admin only, scrapers authenticate with an admin's bearer token
+#[instrument(skip(state))]
+pub async fn get_metrics(
+    user: AuthUser,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    user.require_admin()?;
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(),
+    ))
+}
@@ -1,5 +1,11 @@
+mod admin_handler;
 pub mod app_router;
 pub mod auth_handler;
+mod features_handler;
 mod file_handler;
 mod keys_handler;
+mod metrics_handler;
+mod version_handler;
 mod ws_handler;
+
+pub use ws_handler::ws_router::close_connections_for_shutdown;
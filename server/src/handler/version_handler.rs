@@ -0,0 +1,21 @@
+use axum::Json;
+use chrono::DateTime;
+use tracing::{info, instrument};
+
+use crate::dtos::VersionRespDto;
+
+#[instrument]
+pub async fn get_version() -> Json<VersionRespDto> {
+    info!("Getting server version");
+    // Set by build.rs
+    let build_time = env!("BUILD_TIMESTAMP")
+        .parse()
+        .ok()
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        .unwrap_or_default();
+    Json(VersionRespDto {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        git_sha: env!("GIT_SHA").to_string(),
+        build_time,
+    })
+}
@@ -0,0 +1,94 @@
+use tracing::{error, info, instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    config::AppState,
+    database::{drafts::DraftRepository, room_members::RoomMemberRepository},
+    dtos::ServerResp,
+    errors::error::AppError,
+};
+
+use super::utils::{send_error, send_event};
+
+/// Drafts may be encrypted blobs, so this bounds bytes rather than characters
+const MAX_DRAFT_BYTES: usize = 64 * 1024;
+
+#[instrument(skip(state, content), fields(user_id = %user_id))]
+pub async fn save_draft_response(state: &&AppState, user_id: Uuid, room_id: Uuid, content: String) {
+    info!("User {} is saving a draft for room {}", user_id, room_id);
+    if content.len() > MAX_DRAFT_BYTES {
+        warn!("Draft of {} bytes is too large", content.len());
+        let _ = send_error(state, user_id, AppError::InvalidRequestFormat);
+        return;
+    }
+
+    if !check_member(state, user_id, room_id).await {
+        return;
+    }
+
+    let _ = match state.db.save_draft(user_id, room_id, &content).await {
+        Ok(draft) => {
+            let _ = send_event(
+                state,
+                user_id,
+                ServerResp::DraftSaved {
+                    room_id,
+                    updated_at: draft.updated_at,
+                },
+            );
+        }
+        Err(e) => {
+            error!("Database error saving draft for room {}: {:?}", room_id, e);
+            let _ = send_error(state, user_id, AppError::Internal);
+        }
+    };
+}
+
+#[instrument(skip(state), fields(user_id = %user_id))]
+pub async fn get_draft_response(state: &&AppState, user_id: Uuid, room_id: Uuid) {
+    info!(
+        "User {} is requesting their draft for room {}",
+        user_id, room_id
+    );
+    if !check_member(state, user_id, room_id).await {
+        return;
+    }
+
+    let _ = match state.db.get_draft(user_id, room_id).await {
+        Ok(draft) => {
+            let _ = send_event(
+                state,
+                user_id,
+                ServerResp::Draft {
+                    room_id,
+                    updated_at: draft.as_ref().map(|d| d.updated_at),
+                    content: draft.map(|d| d.content),
+                },
+            );
+        }
+        Err(e) => {
+            error!("Database error getting draft for room {}: {:?}", room_id, e);
+            let _ = send_error(state, user_id, AppError::Internal);
+        }
+    };
+}
+
+/// Whether the user is in the room; otherwise the error has already been sent
+async fn check_member(state: &&AppState, user_id: Uuid, room_id: Uuid) -> bool {
+    match state.db.is_member(room_id, user_id).await {
+        Ok(true) => true,
+        Ok(false) => {
+            warn!("User {} is not a member of room {}", user_id, room_id);
+            let _ = send_error(state, user_id, AppError::NotRoomMember);
+            false
+        }
+        Err(e) => {
+            error!(
+                "Database error checking membership of user {} in room {}: {:?}",
+                user_id, room_id, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+            false
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use chrono::{Duration, Utc};
 use tracing::{debug, error, info, instrument, warn};
 use uuid::Uuid;
 
@@ -7,8 +8,9 @@ use crate::{
         invitations::InvitationRepository, models::InvitationStatus,
         room_members::RoomMemberRepository, rooms::RoomRepository, users::UserRepository,
     },
-    dtos::{InvitationInfo, ServerResp},
+    dtos::{InvitationInfo, RoomInvitationInfo, ServerResp},
     errors::error::AppError,
+    utils::validation::validate_username,
 };
 
 use super::utils::{send_error, send_event};
@@ -19,7 +21,20 @@ pub async fn invite_response(state: &&AppState, user_id: Uuid, room_id: Uuid, us
         "User {} is attempting to invite {} to room {}",
         user_id, username, room_id
     );
+    // A name no account could have would only surface as UserNotFound
+    let username = username.trim().to_string();
+    if !validate_username(&username).is_empty() {
+        warn!("Invite failed: invalid username '{}'", username);
+        let _ = send_error(state, user_id, AppError::InvalidRequestFormat);
+        return;
+    }
+
     let room_name = match state.db.get_room_by_id(room_id).await {
+        Ok(Some(room)) if room.is_direct => {
+            warn!("Room {} is a direct message room", room_id);
+            let _ = send_error(state, user_id, AppError::DirectMessageRoom);
+            return;
+        }
         Ok(Some(room)) => room.name,
         Ok(None) => {
             warn!("Room not found: {}", room_id);
@@ -90,6 +105,47 @@ pub async fn invite_response(state: &&AppState, user_id: Uuid, room_id: Uuid, us
         _ => {}
     };
 
+    // An invitation into a full room could never be accepted
+    let _ = match state.db.get_members(room_id).await {
+        Ok(members) if members.len() >= state.config.max_room_members => {
+            warn!(
+                "Invite failed: Room {} is full with {} members",
+                room_id,
+                members.len()
+            );
+            let _ = send_error(state, user_id, AppError::RoomFull);
+            return;
+        }
+        Err(e) => {
+            error!("Database error getting room members: {:?}", e);
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+        _ => {}
+    };
+
+    // Keeps a group of members from burying someone in invitations
+    let _ = match state
+        .db
+        .count_pending_invitations_for_user(invitee.id)
+        .await
+    {
+        Ok(pending) if pending >= state.config.max_pending_invitations => {
+            warn!(
+                "Invite failed: User {} already has {} pending invitations",
+                username, pending
+            );
+            let _ = send_error(state, user_id, AppError::RateLimited);
+            return;
+        }
+        Err(e) => {
+            error!("Database error counting pending invitations: {:?}", e);
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+        _ => {}
+    };
+
     let _ = match state
         .db
         .create_invitation(
@@ -99,6 +155,7 @@ pub async fn invite_response(state: &&AppState, user_id: Uuid, room_id: Uuid, us
             invitee.username.clone(),
             inviter.id,
             inviter.username.clone(),
+            Utc::now() + Duration::seconds(state.config.invitation_ttl_secs),
         )
         .await
     {
@@ -115,6 +172,7 @@ pub async fn invite_response(state: &&AppState, user_id: Uuid, room_id: Uuid, us
                     room_id: invitation.room_id,
                     room_name: invitation.room_name.clone(),
                     invitee_username: invitation.invitee_username,
+                    created_at: invitation.created_at,
                 },
             );
             let _ = send_event(
@@ -125,6 +183,7 @@ pub async fn invite_response(state: &&AppState, user_id: Uuid, room_id: Uuid, us
                     room_id: invitation.room_id,
                     room_name: invitation.room_name,
                     inviter_username: invitation.inviter_username,
+                    created_at: invitation.created_at,
                 },
             );
         }
@@ -166,6 +225,15 @@ pub async fn decline_invitation_response(state: &&AppState, user_id: Uuid, invit
         }
     };
 
+    if !invitation.is_pending() {
+        warn!(
+            "Invitation {} is no longer pending and can't be declined",
+            invitation_id
+        );
+        let _ = send_error(state, user_id, AppError::NoPendingInvitation);
+        return;
+    }
+
     let _ = match state.db.get_room_by_id(invitation.room_id).await {
         Ok(Some(_)) => {}
         _ => {
@@ -257,3 +325,166 @@ pub async fn get_pending_invitations_response(state: &&AppState, user_id: Uuid)
         }
     };
 }
+
+#[instrument(skip(state), fields(user_id = %user_id))]
+pub async fn get_pending_invitation_count_response(state: &&AppState, user_id: Uuid) {
+    info!(
+        "User {} is requesting their pending invitation count",
+        user_id
+    );
+    let _ = match state.db.count_pending_invitations_for_user(user_id).await {
+        Ok(count) => {
+            let _ = send_event(state, user_id, ServerResp::PendingInvitationCount { count });
+        }
+        Err(e) => {
+            error!(
+                "Database error counting pending invitations for user {}: {:?}",
+                user_id, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+        }
+    };
+}
+
+#[instrument(skip(state), fields(user_id = %user_id))]
+pub async fn get_room_invitations_response(state: &&AppState, user_id: Uuid, room_id: Uuid) {
+    info!(
+        "User {} is requesting pending invitations for room {}",
+        user_id, room_id
+    );
+    let _ = match state.db.get_room_by_id(room_id).await {
+        Ok(None) => {
+            warn!("Room not found: {}", room_id);
+            let _ = send_error(state, user_id, AppError::RoomNotFound);
+            return;
+        }
+        Err(e) => {
+            error!("Failed to get room by id: {}: {:?}", room_id, e);
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+        Ok(Some(_)) => {}
+    };
+
+    let _ = match state.db.is_admin(room_id, user_id).await {
+        Ok(false) => {
+            warn!("User {} is not an admin of room {}", user_id, room_id);
+            let _ = send_error(state, user_id, AppError::NotRoomAdmin);
+            return;
+        }
+        Err(e) => {
+            error!("Failed to check if user is an admin of room: {:?}", e);
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+        _ => {}
+    };
+
+    let _ = match state.db.get_pending_invitations_for_room(room_id).await {
+        Ok(invitations) => {
+            let invitations = invitations
+                .into_iter()
+                .map(|inv| RoomInvitationInfo {
+                    invitation_id: inv.id,
+                    invitee_username: inv.invitee_username,
+                    inviter_username: inv.inviter_username,
+                    created_at: inv.created_at,
+                })
+                .collect::<Vec<RoomInvitationInfo>>();
+            info!(
+                "Sending {} pending invitations of room {} to user {}",
+                invitations.len(),
+                room_id,
+                user_id
+            );
+            let _ = send_event(
+                state,
+                user_id,
+                ServerResp::RoomInvitations {
+                    room_id,
+                    invitations,
+                },
+            );
+        }
+        Err(e) => {
+            error!(
+                "Database error getting pending invitations for room {}: {:?}",
+                room_id, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+    };
+}
+
+/// Lets an invitee peek at a room before deciding on a pending invitation
+#[instrument(skip(state), fields(user_id = %user_id))]
+pub async fn preview_room_response(state: &&AppState, user_id: Uuid, invitation_id: Uuid) {
+    info!(
+        "User {} is requesting a preview for invitation {}",
+        user_id, invitation_id
+    );
+    let invitation = match state.db.get_invitation_by_id(invitation_id).await {
+        // Don't reveal other users' invitations
+        Ok(Some(invitation)) if invitation.invitee_id == user_id && invitation.is_pending() => {
+            invitation
+        }
+        Ok(_) => {
+            warn!(
+                "No pending invitation {} for user {}",
+                invitation_id, user_id
+            );
+            let _ = send_error(state, user_id, AppError::InvitationNotFound);
+            return;
+        }
+        Err(e) => {
+            error!(
+                "Database error getting invitation by id {}: {:?}",
+                invitation_id, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+    };
+
+    let room = match state.db.get_room_by_id(invitation.room_id).await {
+        Ok(Some(room)) => room,
+        Ok(None) => {
+            warn!("Room not found: {}", invitation.room_id);
+            let _ = send_error(state, user_id, AppError::RoomNotFound);
+            return;
+        }
+        Err(e) => {
+            error!("Failed to get room by id: {}: {:?}", invitation.room_id, e);
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+    };
+
+    let members = match state.db.get_members(room.id).await {
+        Ok(members) => members,
+        Err(e) => {
+            error!("Failed to get members of room: {}: {:?}", room.id, e);
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+    };
+
+    let admin_username = members
+        .iter()
+        .find(|m| m.user_id == room.admin_id)
+        .map(|m| m.username.clone())
+        .unwrap_or(room.admin_username);
+
+    let _ = send_event(
+        state,
+        user_id,
+        ServerResp::RoomPreview {
+            invitation_id,
+            room_id: room.id,
+            room_name: room.name,
+            member_count: members.len() as i64,
+            admin_username,
+        },
+    );
+}
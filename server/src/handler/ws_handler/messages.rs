@@ -1,17 +1,43 @@
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
 use tracing::{debug, error, info, instrument, warn};
 use uuid::Uuid;
 
 use crate::{
     config::AppState,
     database::{
-        models::MessageType, room_members::RoomMemberRepository, rooms::RoomRepository,
-        user_messages::MessageRepository, users::UserRepository,
+        files::FileRepository,
+        models::{MessageType, UserMessage, UserRole},
+        room_members::RoomMemberRepository,
+        rooms::RoomRepository,
+        user_messages::MessageRepository,
+        users::UserRepository,
     },
-    dtos::{MessageInfo, ServerResp},
+    dtos::{MessageInfo, RoomMessageMatches, ServerResp},
     errors::error::AppError,
+    utils::{
+        cursor::{decode_cursor, encode_cursor},
+        validation::{normalize_message_content, validate_message_content},
+    },
+};
+
+use super::{
+    reactions::attach_reactions,
+    utils::{send_error, send_event},
 };
 
-use super::utils::{send_error, send_event};
+/// Clients repeat `Typing` while the user keeps typing; older state is stale
+const TYPING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest reply chain a new reply may extend
+const MAX_REPLY_DEPTH: i32 = 32;
+
+/// Shorter queries match nearly every message
+const MIN_MESSAGE_SEARCH_LEN: usize = 2;
+
+/// Upper bound on message search results, whatever limit is asked for
+const MAX_MESSAGE_SEARCH_RESULTS: i64 = 100;
 
 #[instrument(skip(state), fields(user_id = %user_id))]
 pub async fn send_message_response(
@@ -20,9 +46,18 @@ pub async fn send_message_response(
     room_id: Uuid,
     content: String,
     message_type: Option<MessageType>,
+    file_id: Option<Uuid>,
+    reply_to: Option<Uuid>,
 ) {
     info!("User {} is sending message to room {}", user_id, room_id);
-    let message_type = message_type.unwrap_or(MessageType::Text);
+    let content = normalize_message_content(&content);
+    let errors = validate_message_content(&content, state.config.max_message_len);
+    if !errors.is_empty() {
+        let _ = send_error(state, user_id, AppError::InvalidMessageContent(errors));
+        return;
+    }
+
+    let message_type = message_type.unwrap_or_default();
     let room_name = match state.db.get_room_by_id(room_id).await {
         Ok(Some(room)) => room.name,
         Ok(None) => {
@@ -54,6 +89,65 @@ pub async fn send_message_response(
         _ => {}
     };
 
+    if let Some(file_id) = file_id {
+        let _ = match state.db.get_file(file_id).await {
+            Ok(None) => {
+                warn!("File not found: {}", file_id);
+                let _ = send_error(state, user_id, AppError::FileNotFound);
+                return;
+            }
+            Ok(Some(file)) if file.uploader_id != Some(user_id) => {
+                warn!("User {} did not upload file {}", user_id, file_id);
+                let _ = send_error(state, user_id, AppError::NotFileOwner);
+                return;
+            }
+            Err(e) => {
+                error!("Failed to get file by id: {}: {:?}", file_id, e);
+                let _ = send_error(state, user_id, AppError::Internal);
+                return;
+            }
+            Ok(Some(_)) => {}
+        };
+    }
+
+    // Replies can only point at a message in the same room
+    if let Some(reply_to) = reply_to {
+        let _ = match state.db.get_message_by_id(reply_to).await {
+            Ok(Some(parent)) if parent.room_id == room_id => {}
+            Ok(_) => {
+                warn!("Reply target {} not found in room {}", reply_to, room_id);
+                let _ = send_error(state, user_id, AppError::MessageNotFound);
+                return;
+            }
+            Err(e) => {
+                error!("Database error getting message by id {}: {:?}", reply_to, e);
+                let _ = send_error(state, user_id, AppError::Internal);
+                return;
+            }
+        };
+
+        // Parents are always older messages, so a loop means corrupted data
+        let _ = match state.db.get_reply_depth(reply_to, MAX_REPLY_DEPTH).await {
+            Ok(Some(depth)) if depth < MAX_REPLY_DEPTH => {}
+            Ok(depth) => {
+                warn!(
+                    "Rejecting reply to {}: chain depth {:?} (None means a cycle)",
+                    reply_to, depth
+                );
+                let _ = send_error(state, user_id, AppError::InvalidRequestFormat);
+                return;
+            }
+            Err(e) => {
+                error!(
+                    "Database error getting reply depth of message {}: {:?}",
+                    reply_to, e
+                );
+                let _ = send_error(state, user_id, AppError::Internal);
+                return;
+            }
+        };
+    }
+
     let author = match state.db.get_user_by_id(user_id).await {
         Ok(Some(user)) => user,
         _ => {
@@ -69,9 +163,11 @@ pub async fn send_message_response(
             room_id,
             room_name,
             Some(author.id),
-            Some(author.username),
+            Some(author.username.clone()),
             &content,
             message_type,
+            file_id,
+            reply_to,
         )
         .await
     {
@@ -86,11 +182,8 @@ pub async fn send_message_response(
         }
     };
 
-    let member_ids = match state.db.get_members(room_id).await {
-        Ok(members) => members
-            .into_iter()
-            .map(|m| m.user_id)
-            .collect::<Vec<Uuid>>(),
+    let member_ids = match state.db.get_member_ids(room_id).await {
+        Ok(member_ids) => member_ids,
         Err(e) => {
             error!(
                 "Database error getting members for room {}: {:?}",
@@ -101,6 +194,9 @@ pub async fn send_message_response(
         }
     };
 
+    // Clear the sender's indicator right away instead of waiting for it to time out
+    clear_typing(state, room_id, user_id, &author.username, &member_ids);
+
     info!(
         "Broadcasting message {} to room {} members",
         message.id, room_id
@@ -113,7 +209,9 @@ pub async fn send_message_response(
         author_username: message.author_username,
         content: message.content.clone(),
         message_type: message.message_type,
+        file_id: message.file_id,
         created_at: message.created_at,
+        reply_to: message.reply_to,
     };
 
     for member_id in member_ids {
@@ -142,7 +240,9 @@ pub async fn send_message_response(
             room_name: message.room_name,
             content: message.content,
             message_type: message.message_type,
+            file_id: message.file_id,
             created_at: message.created_at,
+            reply_to: message.reply_to,
         },
     );
 }
@@ -155,6 +255,13 @@ pub async fn edit_message_response(
     new_content: String,
 ) {
     info!("User {} is editing message {}", user_id, message_id);
+    let new_content = normalize_message_content(&new_content);
+    let errors = validate_message_content(&new_content, state.config.max_message_len);
+    if !errors.is_empty() {
+        let _ = send_error(state, user_id, AppError::InvalidMessageContent(errors));
+        return;
+    }
+
     let message = match state.db.get_message_by_id(message_id).await {
         Ok(Some(message)) => message,
         Ok(None) => {
@@ -172,6 +279,16 @@ pub async fn edit_message_response(
         }
     };
 
+    // Checked first, as system messages have no author to compare with
+    if message.message_type != MessageType::Text {
+        warn!(
+            "Message {} is a {} message and can't be edited",
+            message_id, message.message_type
+        );
+        let _ = send_error(state, user_id, AppError::InvalidRequestFormat);
+        return;
+    }
+
     if message.author_id != Some(user_id) {
         warn!(
             "User {} is not the author of message {}",
@@ -191,10 +308,15 @@ pub async fn edit_message_response(
             let event = ServerResp::MessageEdited {
                 message_id: updated_message.id,
                 new_content: updated_message.content.clone(),
+                status: updated_message.status,
+                edited_at: updated_message.edited_at,
             };
-            if let Ok(members) = state.db.get_members(updated_message.room_id).await {
-                for member in members {
-                    let _ = send_event(state, member.user_id, event.clone());
+            if let Ok(member_ids) = state.db.get_member_ids(updated_message.room_id).await {
+                for member_id in member_ids {
+                    if member_id == user_id {
+                        continue;
+                    }
+                    let _ = send_event(state, member_id, event.clone());
                 }
             } else {
                 error!(
@@ -204,6 +326,18 @@ pub async fn edit_message_response(
                 let _ = send_error(state, user_id, AppError::Internal);
                 return;
             }
+
+            let _ = send_event(
+                state,
+                user_id,
+                ServerResp::MessageEditConfirmed {
+                    message_id: updated_message.id,
+                    room_id: updated_message.room_id,
+                    new_content: updated_message.content,
+                    status: updated_message.status,
+                    edited_at: updated_message.edited_at,
+                },
+            );
         }
         _ => {
             error!(
@@ -218,8 +352,8 @@ pub async fn edit_message_response(
 #[instrument(skip(state), fields(user_id = %user_id))]
 pub async fn delete_message_response(state: &&AppState, user_id: Uuid, message_id: Uuid) {
     info!("User {} is deleting message {}", user_id, message_id);
-    let _ = match state.db.get_message_by_id(message_id).await {
-        Ok(Some(_)) => {}
+    let message = match state.db.get_message_by_id(message_id).await {
+        Ok(Some(message)) => message,
         Ok(None) => {
             warn!("Message not found: {}", message_id);
             let _ = send_error(state, user_id, AppError::MessageNotFound);
@@ -235,13 +369,46 @@ pub async fn delete_message_response(state: &&AppState, user_id: Uuid, message_i
         }
     };
 
+    let is_moderator = match is_moderator(state, user_id, message.room_id).await {
+        Ok(is_moderator) => is_moderator,
+        Err(e) => {
+            error!(
+                "Database error checking moderation rights of user {} in room {}: {:?}",
+                user_id, message.room_id, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+    };
+
+    if !is_moderator {
+        if message.author_id != Some(user_id) {
+            warn!(
+                "User {} is not the author of message {}",
+                user_id, message_id
+            );
+            let _ = send_error(state, user_id, AppError::NotMessageAuthor);
+            return;
+        }
+
+        let window = state.config.message_delete_window_secs;
+        if window > 0 && message.created_at < Utc::now() - chrono::Duration::seconds(window) {
+            warn!(
+                "Message {} is older than the {}s delete window",
+                message_id, window
+            );
+            let _ = send_error(state, user_id, AppError::DeleteWindowExpired);
+            return;
+        }
+    }
+
     let _ = match state.db.delete_message(message_id).await {
         Ok(Some(message)) => {
             info!("User {} deleted message {}", user_id, message_id);
             let event = ServerResp::MessageDeleted { message_id };
-            if let Ok(members) = state.db.get_members(message.room_id).await {
-                for member in members {
-                    let _ = send_event(state, member.user_id, event.clone());
+            if let Ok(member_ids) = state.db.get_member_ids(message.room_id).await {
+                for member_id in member_ids {
+                    let _ = send_event(state, member_id, event.clone());
                 }
             } else {
                 error!(
@@ -259,6 +426,19 @@ pub async fn delete_message_response(state: &&AppState, user_id: Uuid, message_i
     };
 }
 
+/// Room admins and server admins may delete anyone's messages at any age
+async fn is_moderator(state: &AppState, user_id: Uuid, room_id: Uuid) -> Result<bool, sqlx::Error> {
+    if state.db.is_admin(room_id, user_id).await? {
+        return Ok(true);
+    }
+
+    Ok(state
+        .db
+        .get_user_by_id(user_id)
+        .await?
+        .is_some_and(|user| user.role == UserRole::Admin))
+}
+
 #[instrument(skip(state), fields(user_id = %user_id))]
 pub async fn get_messages_response(
     state: &&AppState,
@@ -285,49 +465,561 @@ pub async fn get_messages_response(
         }
     };
 
+    if limit < 0 || offset < 0 {
+        send_invalid_page(state, user_id, room_id, limit, offset).await;
+        return;
+    }
+
+    // One extra row tells whether older messages follow, so the member's
+    // visible history never has to be counted for a valid page
     let _ = match state
         .db
-        .get_room_messages(room_id, user_id, limit, offset)
+        .get_room_messages(room_id, user_id, limit.saturating_add(1), offset)
         .await
     {
-        Ok(messages) => {
-            if let Err(e) = state.db.reset_last_read_and_count(room_id, user_id).await {
-                error!(
-                    "Failed to update last_read_at for user {} in room {}: {:?}",
-                    user_id, room_id, e
-                );
+        // An empty page is only valid at the very start of an empty history
+        Ok(messages) if offset > 0 && messages.is_empty() => {
+            send_invalid_page(state, user_id, room_id, limit, offset).await;
+        }
+        Ok(mut messages) => {
+            let has_more = messages.len() as i64 > limit;
+            if has_more {
+                // Oldest first, so the extra row leads the page
+                messages.remove(0);
             }
+            send_message_history(state, user_id, room_id, room_name, messages, has_more, None).await
+        }
+        Err(_) => {
+            let _ = send_error(state, user_id, AppError::Internal);
+        }
+    };
+}
 
-            let mut message_infos = Vec::new();
-            for msg in messages {
-                message_infos.push(MessageInfo {
-                    message_id: msg.id,
-                    author_username: msg.author_username,
-                    content: msg.content,
-                    message_type: msg.message_type,
-                    message_status: msg.status,
-                    created_at: msg.created_at,
-                });
+/// Rejects a page outside the member's history, telling them how many
+/// messages they can see
+async fn send_invalid_page(
+    state: &&AppState,
+    user_id: Uuid,
+    room_id: Uuid,
+    limit: i64,
+    offset: i64,
+) {
+    let total = match state.db.count_room_messages(room_id, user_id).await {
+        Ok(total) => total,
+        Err(e) => {
+            error!(
+                "Database error counting messages in room {}: {:?}",
+                room_id, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+    };
+    warn!(
+        "Invalid page requested: limit {} offset {} of {} messages",
+        limit, offset, total
+    );
+    let _ = send_error(state, user_id, AppError::InvalidPage(total));
+}
+
+#[instrument(skip(state), fields(user_id = %user_id))]
+pub async fn get_messages_before_response(
+    state: &&AppState,
+    user_id: Uuid,
+    room_id: Uuid,
+    before: Option<String>,
+    limit: i64,
+) {
+    info!(
+        "User {} is requesting messages before {:?} for room {}",
+        user_id, before, room_id
+    );
+    let room_name = match state.db.get_room_by_id(room_id).await {
+        Ok(Some(room)) => room.name,
+        Ok(None) => {
+            warn!("Room not found: {}", room_id);
+            let _ = send_error(state, user_id, AppError::RoomNotFound);
+            return;
+        }
+        Err(e) => {
+            error!("Database error getting room by id {}: {:?}", room_id, e);
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+    };
+
+    if limit < 0 {
+        warn!("Invalid page requested: limit {}", limit);
+        let _ = send_error(state, user_id, AppError::InvalidRequestFormat);
+        return;
+    }
+
+    let position = match before
+        .as_deref()
+        .map(|cursor| decode_cursor(cursor, state.config.jwt_secret.as_bytes()))
+        .transpose()
+    {
+        Ok(position) => position,
+        Err(e) => {
+            warn!("Invalid cursor from user {}", user_id);
+            let _ = send_error(state, user_id, e);
+            return;
+        }
+    };
+
+    // One extra row tells whether there is anything older left
+    let _ = match state
+        .db
+        .get_room_messages_before(room_id, user_id, position, limit.saturating_add(1))
+        .await
+    {
+        Ok(mut messages) => {
+            let has_more = messages.len() as i64 > limit;
+            if has_more {
+                messages.remove(0);
             }
-            info!(
-                "Sending {} messages to user {} for room {}",
-                message_infos.len(),
+            let next_cursor = match messages.first() {
+                Some(oldest) if has_more => Some(encode_cursor(
+                    oldest.created_at,
+                    oldest.id,
+                    state.config.jwt_secret.as_bytes(),
+                )),
+                _ => None,
+            };
+            send_message_history(
+                state,
                 user_id,
-                room_id
+                room_id,
+                room_name,
+                messages,
+                has_more,
+                next_cursor,
+            )
+            .await
+        }
+        Err(e) => {
+            error!(
+                "Database error getting messages before {:?} in room {}: {:?}",
+                before, room_id, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+        }
+    };
+}
+
+/// Marks the room read, sends a page of history, oldest message first, and
+/// tells the other members how far this user has read
+async fn send_message_history(
+    state: &&AppState,
+    user_id: Uuid,
+    room_id: Uuid,
+    room_name: String,
+    messages: Vec<UserMessage>,
+    has_more: bool,
+    next_cursor: Option<String>,
+) {
+    // Captured before the reset so clients can place a "new messages" divider
+    let (last_read_at, read_at) = match state.db.reset_last_read_and_count(room_id, user_id).await {
+        Ok(Some((last_read_at, read_at))) => (Some(last_read_at), Some(read_at)),
+        Ok(None) => (None, None),
+        Err(e) => {
+            error!(
+                "Failed to update last_read_at for user {} in room {}: {:?}",
+                user_id, room_id, e
+            );
+            (None, None)
+        }
+    };
+
+    let mut message_infos: Vec<MessageInfo> = messages.into_iter().map(MessageInfo::from).collect();
+    attach_reactions(state, &mut message_infos).await;
+    info!(
+        "Sending {} messages to user {} for room {}",
+        message_infos.len(),
+        user_id,
+        room_id
+    );
+    debug!("Messages: {:?}", message_infos);
+    let _ = send_event(
+        state,
+        user_id,
+        ServerResp::MessageHistory {
+            room_id,
+            room_name,
+            messages: message_infos,
+            last_read_at,
+            has_more,
+            next_cursor,
+        },
+    );
+
+    if let Some(read_at) = read_at {
+        broadcast_messages_read(state, user_id, room_id, read_at).await;
+    }
+}
+
+/// Read receipts are best effort, so failures are only logged
+async fn broadcast_messages_read(
+    state: &&AppState,
+    user_id: Uuid,
+    room_id: Uuid,
+    read_at: DateTime<Utc>,
+) {
+    let members = match state.db.get_members(room_id).await {
+        Ok(members) => members,
+        Err(e) => {
+            error!(
+                "Database error getting members for room {}: {:?}",
+                room_id, e
+            );
+            return;
+        }
+    };
+
+    let username = match members.iter().find(|m| m.user_id == user_id) {
+        Some(member) => member.username.clone(),
+        None => return,
+    };
+
+    let event = ServerResp::MessagesRead {
+        room_id,
+        username,
+        read_at,
+    };
+    for member in members.iter().filter(|m| m.user_id != user_id) {
+        let _ = send_event(state, member.user_id, event.clone());
+    }
+}
+
+#[instrument(skip(state), fields(user_id = %user_id))]
+pub async fn get_thread_response(
+    state: &&AppState,
+    user_id: Uuid,
+    root_message_id: Uuid,
+    limit: i64,
+    offset: i64,
+) {
+    info!(
+        "User {} is requesting thread of message {}",
+        user_id, root_message_id
+    );
+    let root = match state.db.get_message_by_id(root_message_id).await {
+        Ok(Some(message)) => message,
+        Ok(None) => {
+            warn!("Message not found: {}", root_message_id);
+            let _ = send_error(state, user_id, AppError::MessageNotFound);
+            return;
+        }
+        Err(e) => {
+            error!(
+                "Database error getting message by id {}: {:?}",
+                root_message_id, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+    };
+
+    let _ = match state.db.is_member(root.room_id, user_id).await {
+        Ok(false) => {
+            warn!("User {} is not a member of room {}", user_id, root.room_id);
+            let _ = send_error(state, user_id, AppError::NotRoomMember);
+            return;
+        }
+        Err(e) => {
+            error!(
+                "Database error checking membership for user {} in room {}: {:?}",
+                user_id, root.room_id, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+        _ => {}
+    };
+
+    if limit < 0 || offset < 0 {
+        warn!("Invalid thread page: limit {} offset {}", limit, offset);
+        let _ = send_error(state, user_id, AppError::InvalidRequestFormat);
+        return;
+    }
+
+    let _ = match state
+        .db
+        .get_thread_messages(root_message_id, user_id, limit, offset)
+        .await
+    {
+        Ok(messages) => {
+            let _ = send_event(
+                state,
+                user_id,
+                ServerResp::Thread {
+                    root_message_id,
+                    room_id: root.room_id,
+                    messages: messages.into_iter().map(MessageInfo::from).collect(),
+                },
+            );
+        }
+        Err(e) => {
+            error!(
+                "Database error getting thread of message {}: {:?}",
+                root_message_id, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+        }
+    };
+}
+
+#[instrument(skip(state), fields(user_id = %user_id))]
+pub async fn search_all_messages_response(
+    state: &&AppState,
+    user_id: Uuid,
+    query: String,
+    limit: i64,
+) {
+    info!(
+        "User {} is searching messages with query '{}'",
+        user_id, query
+    );
+    let limit = match message_search_limit(state, &query, limit) {
+        Some(limit) => limit,
+        None => {
+            let _ = send_error(state, user_id, AppError::InvalidRequestFormat);
+            return;
+        }
+    };
+
+    let _ = match state.db.search_messages(user_id, None, &query, limit).await {
+        Ok(messages) => {
+            info!(
+                "Found {} messages matching query '{}' for user {}",
+                messages.len(),
+                query,
+                user_id
+            );
+            let mut rooms: Vec<RoomMessageMatches> = Vec::new();
+            for message in messages {
+                let index = match rooms.iter().position(|r| r.room_id == message.room_id) {
+                    Some(index) => index,
+                    None => {
+                        rooms.push(RoomMessageMatches {
+                            room_id: message.room_id,
+                            room_name: message.room_name.clone(),
+                            messages: Vec::new(),
+                        });
+                        rooms.len() - 1
+                    }
+                };
+                rooms[index].messages.push(MessageInfo::from(message));
+            }
+            let _ = send_event(state, user_id, ServerResp::MessagesFound { rooms });
+        }
+        Err(e) => {
+            error!(
+                "Database error searching messages with query '{}': {:?}",
+                query, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+        }
+    };
+}
+
+#[instrument(skip(state), fields(user_id = %user_id))]
+pub async fn search_messages_response(
+    state: &&AppState,
+    user_id: Uuid,
+    room_id: Uuid,
+    query: String,
+    limit: i64,
+) {
+    info!(
+        "User {} is searching messages in room {} with query '{}'",
+        user_id, room_id, query
+    );
+    let limit = match message_search_limit(state, &query, limit) {
+        Some(limit) => limit,
+        None => {
+            let _ = send_error(state, user_id, AppError::InvalidRequestFormat);
+            return;
+        }
+    };
+
+    let _ = match state.db.is_member(room_id, user_id).await {
+        Ok(false) => {
+            warn!("User {} is not a member of room {}", user_id, room_id);
+            let _ = send_error(state, user_id, AppError::NotRoomMember);
+            return;
+        }
+        Err(e) => {
+            error!(
+                "Database error checking membership for user {} in room {}: {:?}",
+                user_id, room_id, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+        _ => {}
+    };
+
+    let _ = match state
+        .db
+        .search_messages(user_id, Some(room_id), &query, limit)
+        .await
+    {
+        Ok(messages) => {
+            info!(
+                "Found {} messages in room {} matching query '{}' for user {}",
+                messages.len(),
+                room_id,
+                query,
+                user_id
             );
-            debug!("Messages: {:?}", message_infos);
             let _ = send_event(
                 state,
                 user_id,
-                ServerResp::MessageHistory {
+                ServerResp::MessageSearchResults {
                     room_id,
-                    room_name,
-                    messages: message_infos,
+                    messages: messages.into_iter().map(MessageInfo::from).collect(),
                 },
             );
         }
-        Err(_) => {
+        Err(e) => {
+            error!(
+                "Database error searching messages in room {} with query '{}': {:?}",
+                room_id, query, e
+            );
             let _ = send_error(state, user_id, AppError::Internal);
         }
     };
 }
+
+/// The result limit to search with, capped at `MAX_MESSAGE_SEARCH_RESULTS`;
+/// `None` if the query or limit is out of bounds
+fn message_search_limit(state: &AppState, query: &str, limit: i64) -> Option<i64> {
+    let query_len = query.chars().count();
+    if query_len < MIN_MESSAGE_SEARCH_LEN
+        || query_len > state.config.max_search_query_len
+        || limit < 0
+    {
+        warn!("Invalid message search: query '{}' limit {}", query, limit);
+        return None;
+    }
+    Some(limit.min(MAX_MESSAGE_SEARCH_RESULTS))
+}
+
+/// Drops the user's typing state in a room and, if it was still fresh, tells
+/// `member_ids` they stopped
+pub(super) fn clear_typing(
+    state: &AppState,
+    room_id: Uuid,
+    user_id: Uuid,
+    username: &str,
+    member_ids: &[Uuid],
+) {
+    let was_typing = state
+        .typing
+        .remove(&(room_id, user_id))
+        .is_some_and(|(_, since)| since.elapsed() < TYPING_TIMEOUT);
+    if was_typing {
+        let event = ServerResp::UserTyping {
+            room_id,
+            username: username.to_string(),
+            is_typing: false,
+        };
+        for member_id in member_ids.iter().filter(|id| **id != user_id) {
+            let _ = send_event(state, *member_id, event.clone());
+        }
+    }
+}
+
+/// Clears whatever the user was still typing once their last socket is gone,
+/// so the entries don't outlive the connection
+pub(super) async fn clear_typing_on_disconnect(state: &AppState, user_id: Uuid) {
+    let room_ids: Vec<Uuid> = state
+        .typing
+        .iter()
+        .filter(|entry| entry.key().1 == user_id)
+        .map(|entry| entry.key().0)
+        .collect();
+    if room_ids.is_empty() {
+        return;
+    }
+
+    let username = match state.db.get_user_by_id(user_id).await {
+        Ok(Some(user)) => Some(user.username),
+        Ok(None) => {
+            warn!("User {} not found, dropping typing state", user_id);
+            None
+        }
+        Err(e) => {
+            error!("Database error getting user {}: {:?}", user_id, e);
+            None
+        }
+    };
+    // Without a name the stop can't be announced, so the entries just go
+    let Some(username) = username else {
+        for room_id in room_ids {
+            state.typing.remove(&(room_id, user_id));
+        }
+        return;
+    };
+
+    for room_id in room_ids {
+        // Without members to tell, the entry is still dropped
+        let member_ids = match state.db.get_member_ids(room_id).await {
+            Ok(member_ids) => member_ids,
+            Err(e) => {
+                error!(
+                    "Database error getting members for room {}: {:?}",
+                    room_id, e
+                );
+                Vec::new()
+            }
+        };
+        clear_typing(state, room_id, user_id, &username, &member_ids);
+    }
+}
+
+#[instrument(skip(state), fields(user_id = %user_id))]
+pub async fn typing_response(state: &&AppState, user_id: Uuid, room_id: Uuid, is_typing: bool) {
+    debug!(
+        "User {} typing state in room {}: {}",
+        user_id, room_id, is_typing
+    );
+    // Typing is best effort, so problems are dropped rather than reported
+    let members = match state.db.get_members(room_id).await {
+        Ok(members) => members,
+        Err(e) => {
+            error!(
+                "Database error getting members for room {}: {:?}",
+                room_id, e
+            );
+            return;
+        }
+    };
+
+    let username = match members.iter().find(|m| m.user_id == user_id) {
+        Some(member) => member.username.clone(),
+        None => {
+            debug!(
+                "Ignoring typing from non-member {} in room {}",
+                user_id, room_id
+            );
+            return;
+        }
+    };
+
+    if is_typing {
+        state.typing.insert((room_id, user_id), Instant::now());
+    } else {
+        state.typing.remove(&(room_id, user_id));
+    }
+
+    let event = ServerResp::UserTyping {
+        room_id,
+        username,
+        is_typing,
+    };
+    for member in members.iter().filter(|m| m.user_id != user_id) {
+        let _ = send_event(state, member.user_id, event.clone());
+    }
+}
@@ -1,6 +1,9 @@
+mod drafts;
 mod invitations;
 mod messages;
+mod pins;
+mod reactions;
 mod rooms;
 mod users;
-mod utils;
+pub mod utils;
 pub mod ws_router;
@@ -0,0 +1,187 @@
+use tracing::{debug, error, info, instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    config::AppState,
+    database::{
+        models::{MessageStatus, RoomMember},
+        room_members::RoomMemberRepository,
+        user_messages::MessageRepository,
+    },
+    dtos::{MessageInfo, ServerResp},
+    errors::error::AppError,
+};
+
+use super::{
+    reactions::attach_reactions,
+    utils::{send_error, send_event},
+};
+
+#[instrument(skip(state), fields(user_id = %user_id))]
+pub async fn pin_message_response(state: &&AppState, user_id: Uuid, message_id: Uuid) {
+    info!("User {} is pinning message {}", user_id, message_id);
+    let (room_id, username, members) = match pin_target(state, user_id, message_id).await {
+        Some(target) => target,
+        None => return,
+    };
+
+    let _ = match state.db.pin_message(message_id).await {
+        Ok(Some(_)) => {
+            let event = ServerResp::MessagePinned {
+                message_id,
+                room_id,
+                username,
+            };
+            for member in members {
+                let _ = send_event(state, member.user_id, event.clone());
+            }
+        }
+        // Pinning twice changes nothing
+        Ok(None) => {
+            debug!("Message {} is already pinned", message_id);
+        }
+        Err(e) => {
+            error!("Database error pinning message {}: {:?}", message_id, e);
+            let _ = send_error(state, user_id, AppError::Internal);
+        }
+    };
+}
+
+#[instrument(skip(state), fields(user_id = %user_id))]
+pub async fn unpin_message_response(state: &&AppState, user_id: Uuid, message_id: Uuid) {
+    info!("User {} is unpinning message {}", user_id, message_id);
+    let (room_id, username, members) = match pin_target(state, user_id, message_id).await {
+        Some(target) => target,
+        None => return,
+    };
+
+    let _ = match state.db.unpin_message(message_id).await {
+        Ok(Some(_)) => {
+            let event = ServerResp::MessageUnpinned {
+                message_id,
+                room_id,
+                username,
+            };
+            for member in members {
+                let _ = send_event(state, member.user_id, event.clone());
+            }
+        }
+        Ok(None) => {
+            debug!("Message {} was not pinned", message_id);
+        }
+        Err(e) => {
+            error!("Database error unpinning message {}: {:?}", message_id, e);
+            let _ = send_error(state, user_id, AppError::Internal);
+        }
+    };
+}
+
+#[instrument(skip(state), fields(user_id = %user_id))]
+pub async fn get_pinned_messages_response(state: &&AppState, user_id: Uuid, room_id: Uuid) {
+    info!(
+        "User {} is requesting pinned messages of room {}",
+        user_id, room_id
+    );
+    let _ = match state.db.is_member(room_id, user_id).await {
+        Ok(false) => {
+            warn!("User {} is not a member of room {}", user_id, room_id);
+            let _ = send_error(state, user_id, AppError::NotRoomMember);
+            return;
+        }
+        Err(e) => {
+            error!(
+                "Database error checking membership for user {} in room {}: {:?}",
+                user_id, room_id, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+        _ => {}
+    };
+
+    let _ = match state.db.get_pinned_messages(room_id, user_id).await {
+        Ok(messages) => {
+            let mut messages: Vec<MessageInfo> =
+                messages.into_iter().map(MessageInfo::from).collect();
+            attach_reactions(state, &mut messages).await;
+            let _ = send_event(
+                state,
+                user_id,
+                ServerResp::PinnedMessages { room_id, messages },
+            );
+        }
+        Err(e) => {
+            error!(
+                "Database error getting pinned messages of room {}: {:?}",
+                room_id, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+        }
+    };
+}
+
+/// The message's room, the admin's username and the room's members, once the
+/// message is a live one and the caller is its room's admin; otherwise the
+/// error has already been sent
+async fn pin_target(
+    state: &&AppState,
+    user_id: Uuid,
+    message_id: Uuid,
+) -> Option<(Uuid, String, Vec<RoomMember>)> {
+    let room_id = match state.db.get_message_by_id(message_id).await {
+        Ok(Some(message)) if message.status != MessageStatus::Deleted => message.room_id,
+        Ok(_) => {
+            warn!("Message not found: {}", message_id);
+            let _ = send_error(state, user_id, AppError::MessageNotFound);
+            return None;
+        }
+        Err(e) => {
+            error!(
+                "Database error getting message by id {}: {:?}",
+                message_id, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+            return None;
+        }
+    };
+
+    let _ = match state.db.is_admin(room_id, user_id).await {
+        Ok(false) => {
+            warn!("User {} is not an admin of room {}", user_id, room_id);
+            let _ = send_error(state, user_id, AppError::NotRoomAdmin);
+            return None;
+        }
+        Err(e) => {
+            error!(
+                "Database error checking admin rights of user {} in room {}: {:?}",
+                user_id, room_id, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+            return None;
+        }
+        Ok(true) => {}
+    };
+
+    let members = match state.db.get_members(room_id).await {
+        Ok(members) => members,
+        Err(e) => {
+            error!(
+                "Database error getting members for room {}: {:?}",
+                room_id, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+            return None;
+        }
+    };
+
+    let username = match members.iter().find(|m| m.user_id == user_id) {
+        Some(member) => member.username.clone(),
+        None => {
+            error!("Admin {} of room {} is not a member", user_id, room_id);
+            let _ = send_error(state, user_id, AppError::Internal);
+            return None;
+        }
+    };
+
+    Some((room_id, username, members))
+}
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+
+use tracing::{debug, error, info, instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    config::AppState,
+    database::{
+        models::{MessageStatus, RoomMember, UserMessage},
+        reactions::ReactionRepository,
+        room_members::RoomMemberRepository,
+        user_messages::MessageRepository,
+    },
+    dtos::{MessageInfo, ReactionInfo, ServerResp},
+    errors::error::AppError,
+};
+
+use super::utils::{send_error, send_event};
+
+/// Room for emoji made of several code points, like flags and skin tones
+const MAX_EMOJI_CHARS: usize = 32;
+
+#[instrument(skip(state), fields(user_id = %user_id))]
+pub async fn react_response(state: &&AppState, user_id: Uuid, message_id: Uuid, emoji: String) {
+    info!(
+        "User {} is reacting to message {} with {}",
+        user_id, message_id, emoji
+    );
+    let (message, username, members) =
+        match reaction_target(state, user_id, message_id, &emoji).await {
+            Some(target) => target,
+            None => return,
+        };
+
+    let _ = match state.db.add_reaction(message_id, user_id, &emoji).await {
+        Ok(Some(_)) => {
+            let event = ServerResp::ReactionAdded {
+                message_id,
+                room_id: message.room_id,
+                username,
+                emoji,
+            };
+            for member in members {
+                let _ = send_event(state, member.user_id, event.clone());
+            }
+        }
+        // Reacting twice with the same emoji changes nothing
+        Ok(None) => {
+            debug!(
+                "User {} already reacted to message {} with {}",
+                user_id, message_id, emoji
+            );
+        }
+        Err(e) => {
+            error!(
+                "Database error adding reaction to message {}: {:?}",
+                message_id, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+        }
+    };
+}
+
+#[instrument(skip(state), fields(user_id = %user_id))]
+pub async fn unreact_response(state: &&AppState, user_id: Uuid, message_id: Uuid, emoji: String) {
+    info!(
+        "User {} is removing reaction {} from message {}",
+        user_id, emoji, message_id
+    );
+    let (message, username, members) =
+        match reaction_target(state, user_id, message_id, &emoji).await {
+            Some(target) => target,
+            None => return,
+        };
+
+    let _ = match state.db.remove_reaction(message_id, user_id, &emoji).await {
+        Ok(Some(_)) => {
+            let event = ServerResp::ReactionRemoved {
+                message_id,
+                room_id: message.room_id,
+                username,
+                emoji,
+            };
+            for member in members {
+                let _ = send_event(state, member.user_id, event.clone());
+            }
+        }
+        Ok(None) => {
+            debug!(
+                "User {} had no reaction {} on message {}",
+                user_id, emoji, message_id
+            );
+        }
+        Err(e) => {
+            error!(
+                "Database error removing reaction from message {}: {:?}",
+                message_id, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+        }
+    };
+}
+
+/// The message being reacted to, the reacting member's username and the
+/// room's members, once the emoji and membership check out; otherwise the
+/// error has already been sent
+async fn reaction_target(
+    state: &&AppState,
+    user_id: Uuid,
+    message_id: Uuid,
+    emoji: &str,
+) -> Option<(UserMessage, String, Vec<RoomMember>)> {
+    let emoji_chars = emoji.chars().count();
+    if emoji.trim().is_empty() || emoji_chars > MAX_EMOJI_CHARS {
+        warn!("Invalid reaction emoji of {} chars", emoji_chars);
+        let _ = send_error(state, user_id, AppError::InvalidRequestFormat);
+        return None;
+    }
+
+    let message = match state.db.get_message_by_id(message_id).await {
+        Ok(Some(message)) if message.status != MessageStatus::Deleted => message,
+        Ok(_) => {
+            warn!("Message not found: {}", message_id);
+            let _ = send_error(state, user_id, AppError::MessageNotFound);
+            return None;
+        }
+        Err(e) => {
+            error!(
+                "Database error getting message by id {}: {:?}",
+                message_id, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+            return None;
+        }
+    };
+
+    let members = match state.db.get_members(message.room_id).await {
+        Ok(members) => members,
+        Err(e) => {
+            error!(
+                "Database error getting members for room {}: {:?}",
+                message.room_id, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+            return None;
+        }
+    };
+
+    let username = match members.iter().find(|m| m.user_id == user_id) {
+        Some(member) => member.username.clone(),
+        None => {
+            warn!(
+                "User {} is not a member of room {}",
+                user_id, message.room_id
+            );
+            let _ = send_error(state, user_id, AppError::NotRoomMember);
+            return None;
+        }
+    };
+
+    Some((message, username, members))
+}
+
+/// Fills in the reaction counts of a page of messages. Counts are extra
+/// detail, so on failure the messages are sent without them.
+pub async fn attach_reactions(state: &&AppState, messages: &mut [MessageInfo]) {
+    let message_ids: Vec<Uuid> = messages.iter().map(|m| m.message_id).collect();
+    let counts = match state.db.get_reaction_counts(&message_ids).await {
+        Ok(counts) => counts,
+        Err(e) => {
+            error!("Database error getting reaction counts: {:?}", e);
+            return;
+        }
+    };
+
+    let mut by_message: HashMap<Uuid, Vec<ReactionInfo>> = HashMap::new();
+    for count in counts {
+        by_message
+            .entry(count.message_id)
+            .or_default()
+            .push(ReactionInfo {
+                emoji: count.emoji,
+                count: count.count,
+            });
+    }
+    for message in messages {
+        if let Some(reactions) = by_message.remove(&message.message_id) {
+            message.reactions = reactions;
+        }
+    }
+}
@@ -5,19 +5,34 @@ use uuid::Uuid;
 use crate::{
     config::AppState,
     database::{
-        invitations::InvitationRepository, room_members::RoomMemberRepository,
-        rooms::RoomRepository, users::UserRepository,
+        files::FileRepository, invitations::InvitationRepository,
+        room_members::RoomMemberRepository, rooms::RoomRepository, users::UserRepository,
+    },
+    dtos::{
+        FailedInvitation, FailedRoom, JoinedRoomInfo, MemberInfo, MessageInfo, RoomInfo,
+        RoomSummary, ServerResp, SystemMessageContent,
     },
-    dtos::{MemberInfo, MessageInfo, RoomInfo, ServerResp, SystemMessageContent},
     errors::error::AppError,
+    utils::validation::{normalize_room_name, validate_room_description, validate_username},
 };
 
+use super::messages::clear_typing;
 use crate::handler::ws_handler::utils::{
     create_and_broadcast_system_message, send_error, send_event,
 };
 
+/// Members listed in `RoomInfo`; larger rooms are paged with `GetRoomMembers`
+const ROOM_INFO_MEMBER_LIMIT: i64 = 50;
+
+/// Upper bound on a `GetRoomMembers` page, whatever limit is asked for
+const MAX_MEMBER_PAGE: i64 = 100;
+
+/// Most ids a single batch request may carry
+const MAX_BATCH_SIZE: usize = 50;
+
 #[instrument(skip(state), fields(user_id = %user_id))]
 pub async fn create_room_response(state: &&AppState, user_id: Uuid, name: String) {
+    let name = normalize_room_name(&name);
     info!("Creating room: {}", name);
     let username = match state.db.get_user_by_id(user_id).await {
         Ok(Some(user)) => user.username,
@@ -28,9 +43,45 @@ pub async fn create_room_response(state: &&AppState, user_id: Uuid, name: String
         }
     };
 
+    if state.config.unique_room_names {
+        let _ = match state.db.creator_has_room_named(user_id, &name, None).await {
+            Ok(true) => {
+                warn!("User {} already created a room named {}", user_id, name);
+                let _ = send_error(state, user_id, AppError::InvalidRoomName);
+                return;
+            }
+            Err(e) => {
+                error!("Failed to check room names of user {}: {:?}", user_id, e);
+                let _ = send_error(state, user_id, AppError::Internal);
+                return;
+            }
+            Ok(false) => {}
+        };
+    }
+
     let _ = match state.db.create_room(&name, user_id, username).await {
         Ok(room) => {
             info!("Room created: {} with id {}", room.name, room.id);
+
+            // create_room adds the creator in the same transaction; a room
+            // nobody belongs to could never be administered or deleted
+            let _ = match state.db.get_members(room.id).await {
+                Ok(members) if members.iter().any(|m| m.user_id == user_id) => {}
+                Ok(_) => {
+                    error!(
+                        "Room {} was created without its creator as a member",
+                        room.id
+                    );
+                    let _ = send_error(state, user_id, AppError::Internal);
+                    return;
+                }
+                Err(e) => {
+                    error!("Failed to get members of new room {}: {:?}", room.id, e);
+                    let _ = send_error(state, user_id, AppError::Internal);
+                    return;
+                }
+            };
+
             let _ = send_event(
                 state,
                 user_id,
@@ -49,26 +100,174 @@ pub async fn create_room_response(state: &&AppState, user_id: Uuid, name: String
     };
 }
 
+#[instrument(skip(state), fields(user_id = %user_id))]
+pub async fn start_direct_message_response(state: &&AppState, user_id: Uuid, username: String) {
+    info!(
+        "User {} is starting a direct message with {}",
+        user_id, username
+    );
+    let username = username.trim().to_string();
+    if !validate_username(&username).is_empty() {
+        warn!("Direct message failed: invalid username '{}'", username);
+        let _ = send_error(state, user_id, AppError::InvalidRequestFormat);
+        return;
+    }
+
+    let caller = match state.db.get_user_by_id(user_id).await {
+        Ok(Some(user)) => user,
+        _ => {
+            error!("Failed to get user by id: {}", user_id);
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+    };
+
+    let target = match state.db.get_user_by_username(&username).await {
+        Ok(Some(user)) if user.id == user_id => {
+            warn!("User {} tried to message themselves", user_id);
+            let _ = send_error(state, user_id, AppError::InvalidRequestFormat);
+            return;
+        }
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            warn!("Direct message failed: User {} not found", username);
+            let _ = send_error(state, user_id, AppError::UserNotFound);
+            return;
+        }
+        Err(e) => {
+            error!("Failed to get user by username: {}: {:?}", username, e);
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+    };
+
+    let _ = match state
+        .db
+        .find_or_create_dm(
+            user_id,
+            caller.username.clone(),
+            target.id,
+            target.username.clone(),
+        )
+        .await
+    {
+        Ok((room, created)) => {
+            info!(
+                "Direct message room {} between {} and {} (created: {})",
+                room.id, user_id, target.id, created
+            );
+            // Someone who left the conversation isn't pulled back into it
+            let mut recipients = vec![(user_id, target.username)];
+            match state.db.is_member(room.id, target.id).await {
+                Ok(true) => recipients.push((target.id, caller.username)),
+                Ok(false) => info!("User {} has left room {}", target.id, room.id),
+                Err(e) => error!(
+                    "Database error checking membership for user {} in room {}: {:?}",
+                    target.id, room.id, e
+                ),
+            }
+            for (recipient, other) in recipients {
+                let _ = send_event(
+                    state,
+                    recipient,
+                    ServerResp::DirectMessageStarted {
+                        room_id: room.id,
+                        room_name: room.name.clone(),
+                        username: other,
+                        created_at: room.created_at,
+                    },
+                );
+            }
+        }
+        Err(e) => {
+            error!(
+                "Failed to find or create direct message room for {} and {}: {:?}",
+                user_id, target.id, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+        }
+    };
+}
+
 #[instrument(skip(state), fields(user_id = %user_id))]
 pub async fn join_room_response(state: &&AppState, user_id: Uuid, invitation_id: Uuid) {
     info!(
         "User {} is attempting to join room with invitation {}",
         user_id, invitation_id
     );
+    if let Err(e) = join_room(state, user_id, invitation_id).await {
+        let _ = send_error(state, user_id, e);
+    }
+}
+
+#[instrument(skip(state), fields(user_id = %user_id))]
+pub async fn accept_invitations_response(
+    state: &&AppState,
+    user_id: Uuid,
+    invitation_ids: Vec<Uuid>,
+) {
+    info!(
+        "User {} is accepting {} invitations",
+        user_id,
+        invitation_ids.len()
+    );
+    if invitation_ids.len() > MAX_BATCH_SIZE {
+        warn!(
+            "User {} sent {} invitation ids, more than the limit of {}",
+            user_id,
+            invitation_ids.len(),
+            MAX_BATCH_SIZE
+        );
+        let _ = send_error(state, user_id, AppError::InvalidRequestFormat);
+        return;
+    }
+
+    let mut joined = Vec::new();
+    let mut failed = Vec::new();
+    for invitation_id in invitation_ids {
+        match join_room(state, user_id, invitation_id).await {
+            Ok(room) => joined.push(room),
+            Err(e) => failed.push(FailedInvitation {
+                invitation_id,
+                errors: e.to_api_errors(),
+            }),
+        }
+    }
+
+    info!(
+        "User {} joined {} rooms, {} invitations failed",
+        user_id,
+        joined.len(),
+        failed.len()
+    );
+    let _ = send_event(
+        state,
+        user_id,
+        ServerResp::InvitationsAccepted { joined, failed },
+    );
+}
+
+/// The shared join path: sends `RoomJoined` to the joiner and `MemberJoined`
+/// to the room, leaving errors to the caller
+async fn join_room(
+    state: &&AppState,
+    user_id: Uuid,
+    invitation_id: Uuid,
+) -> Result<JoinedRoomInfo, AppError> {
     let room_id = match state.db.get_invitation_by_id(invitation_id).await {
-        Ok(Some(invitation)) => invitation.room_id,
-        Ok(None) => {
+        Ok(Some(invitation)) if invitation.invitee_id == user_id && invitation.is_pending() => {
+            invitation.room_id
+        }
+        Ok(_) => {
             warn!(
                 "No pending invitation found for invitation id {}",
                 invitation_id
             );
-            let _ = send_error(state, user_id, AppError::NoPendingInvitation);
-            return;
+            return Err(AppError::NoPendingInvitation);
         }
         Err(e) => {
             error!("Failed to get invitation by id: {:?}", e);
-            let _ = send_error(state, user_id, AppError::Internal);
-            return;
+            return Err(AppError::Internal);
         }
     };
 
@@ -76,8 +275,7 @@ pub async fn join_room_response(state: &&AppState, user_id: Uuid, invitation_id:
         Ok(Some(room)) => room,
         _ => {
             error!("Failed to get room by id: {}", room_id);
-            let _ = send_error(state, user_id, AppError::Internal);
-            return;
+            return Err(AppError::Internal);
         }
     };
 
@@ -85,15 +283,18 @@ pub async fn join_room_response(state: &&AppState, user_id: Uuid, invitation_id:
         Ok(members) => members,
         Err(e) => {
             error!("Failed to get room members: {:?}", e);
-            let _ = send_error(state, user_id, AppError::Internal);
-            return;
+            return Err(AppError::Internal);
         }
     };
 
     if members.iter().any(|m| m.user_id == user_id) {
         warn!("User {} is already a member of room {}", user_id, room_id);
-        let _ = send_error(state, user_id, AppError::AlreadyRoomMember);
-        return;
+        return Err(AppError::AlreadyRoomMember);
+    }
+
+    if members.len() >= state.config.max_room_members {
+        warn!("Room {} is full with {} members", room_id, members.len());
+        return Err(AppError::RoomFull);
     }
 
     let admin_username = match members
@@ -104,8 +305,7 @@ pub async fn join_room_response(state: &&AppState, user_id: Uuid, invitation_id:
         Some(username) => username,
         None => {
             error!("Admin user not found in members for room {}", room_id);
-            let _ = send_error(state, user_id, AppError::Internal);
-            return;
+            return Err(AppError::Internal);
         }
     };
 
@@ -113,8 +313,7 @@ pub async fn join_room_response(state: &&AppState, user_id: Uuid, invitation_id:
         Ok(Some(user)) => user.username,
         _ => {
             error!("Failed to get creator user by id: {}", room.creator_id);
-            let _ = send_error(state, user_id, AppError::Internal);
-            return;
+            return Err(AppError::Internal);
         }
     };
 
@@ -122,14 +321,14 @@ pub async fn join_room_response(state: &&AppState, user_id: Uuid, invitation_id:
         Ok(Some(user)) => user.username,
         _ => {
             error!("Failed to get invitee user by id: {}", user_id);
-            let _ = send_error(state, user_id, AppError::Internal);
-            return;
+            return Err(AppError::Internal);
         }
     };
 
     let now = Utc::now();
 
-    let _ = state
+    // Members as of the join itself, so concurrent joiners aren't missed
+    let members = match state
         .db
         .consume_invitations_and_join_room(
             room_id,
@@ -139,10 +338,13 @@ pub async fn join_room_response(state: &&AppState, user_id: Uuid, invitation_id:
             now,
         )
         .await
-        .map_err(|e| {
+    {
+        Ok(members) => members,
+        Err(e) => {
             error!("Failed to consume invitation and join room: {:?}", e);
-            let _ = send_error(state, user_id, AppError::Internal);
-        });
+            return Err(AppError::Internal);
+        }
+    };
 
     let _ = create_and_broadcast_system_message(
         state,
@@ -154,46 +356,98 @@ pub async fn join_room_response(state: &&AppState, user_id: Uuid, invitation_id:
     )
     .await;
 
-    let event = ServerResp::MemberJoined {
-        room_id,
+    info!("User {} joined room {}", user_id, room_id);
+    let joined = JoinedRoomInfo {
+        invitation_id,
+        room_id: room.id,
         room_name: room.name.clone(),
-        username: invitee_username.clone(),
+        admin_username,
+        creator_username,
+        created_at: room.created_at,
         joined_at: now,
     };
-
-    info!("User {} joined room {}", user_id, room_id);
-    for member_id in members.into_iter().map(|m| m.user_id) {
-        let _ = send_event(state, member_id, event.clone());
-    }
-
     let _ = send_event(
         state,
         user_id,
         ServerResp::RoomJoined {
             invitation_id,
-            room_id: room.id,
-            room_name: room.name,
-            admin_username,
-            creator_username,
-            created_at: room.created_at,
-            joined_at: now,
+            room_id: joined.room_id,
+            room_name: joined.room_name.clone(),
+            admin_username: joined.admin_username.clone(),
+            creator_username: joined.creator_username.clone(),
+            created_at: joined.created_at,
+            joined_at: joined.joined_at,
         },
     );
+
+    let event = ServerResp::MemberJoined {
+        room_id,
+        room_name: room.name,
+        username: invitee_username,
+        joined_at: now,
+    };
+    for member_id in members.into_iter().map(|m| m.user_id) {
+        let _ = send_event(state, member_id, event.clone());
+    }
+
+    Ok(joined)
 }
 
 #[instrument(skip(state), fields(user_id = %user_id))]
 pub async fn leave_room_response(state: &&AppState, user_id: Uuid, room_id: Uuid) {
     info!("User {} is attempting to leave room {}", user_id, room_id);
+    if let Err(e) = leave_room(state, user_id, room_id).await {
+        let _ = send_error(state, user_id, e);
+    }
+}
+
+#[instrument(skip(state), fields(user_id = %user_id))]
+pub async fn leave_rooms_response(state: &&AppState, user_id: Uuid, room_ids: Vec<Uuid>) {
+    info!("User {} is leaving {} rooms", user_id, room_ids.len());
+    if room_ids.len() > MAX_BATCH_SIZE {
+        warn!(
+            "User {} sent {} room ids, more than the limit of {}",
+            user_id,
+            room_ids.len(),
+            MAX_BATCH_SIZE
+        );
+        let _ = send_error(state, user_id, AppError::InvalidRequestFormat);
+        return;
+    }
+
+    let mut left = Vec::new();
+    let mut failed = Vec::new();
+    for room_id in room_ids {
+        match leave_room(state, user_id, room_id).await {
+            Ok(()) => left.push(room_id),
+            Err(e) => failed.push(FailedRoom {
+                room_id,
+                errors: e.to_api_errors(),
+            }),
+        }
+    }
+
+    info!(
+        "User {} left {} rooms, {} failed",
+        user_id,
+        left.len(),
+        failed.len()
+    );
+    let _ = send_event(state, user_id, ServerResp::RoomsLeft { left, failed });
+}
+
+/// The shared leave path: announces the departure to the room with a system
+/// message and `MemberLeft`, and sends `RoomLeft` to the leaver, leaving
+/// errors to the caller
+async fn leave_room(state: &&AppState, user_id: Uuid, room_id: Uuid) -> Result<(), AppError> {
     let _ = match state.db.get_room_by_id(room_id).await {
         Ok(None) => {
             warn!("Room not found: {}", room_id);
-            let _ = send_error(state, user_id, AppError::RoomNotFound);
-            return;
+            return Err(AppError::RoomNotFound);
         }
         Err(e) => {
             error!("Failed to get room by id: {}: {:?}", room_id, e);
-            let _ = send_error(state, user_id, AppError::Internal);
-            return;
+            return Err(AppError::Internal);
         }
         Ok(Some(_)) => {}
     };
@@ -201,13 +455,11 @@ pub async fn leave_room_response(state: &&AppState, user_id: Uuid, room_id: Uuid
     let _ = match state.db.is_member(room_id, user_id).await {
         Ok(false) => {
             warn!("User {} is not a member of room {}", user_id, room_id);
-            let _ = send_error(state, user_id, AppError::NotRoomMember);
-            return;
+            return Err(AppError::NotRoomMember);
         }
         Err(e) => {
             error!("Failed to check if user is a member of room: {:?}", e);
-            let _ = send_error(state, user_id, AppError::Internal);
-            return;
+            return Err(AppError::Internal);
         }
         _ => {}
     };
@@ -217,10 +469,10 @@ pub async fn leave_room_response(state: &&AppState, user_id: Uuid, room_id: Uuid
         _ => "Unknown".to_string(),
     };
 
-    let _ = match state.db.leave_room(room_id, user_id).await {
+    match state.db.leave_room(room_id, user_id).await {
         Ok(Some((pending_invs, room))) => {
             info!("User {} left room {}", user_id, room_id);
-            
+
             for inv in pending_invs {
                 let _ = send_event(
                     state,
@@ -237,10 +489,26 @@ pub async fn leave_room_response(state: &&AppState, user_id: Uuid, room_id: Uuid
                 state,
                 room_id,
                 room.name.clone(),
-                SystemMessageContent::Left { username },
+                SystemMessageContent::Left {
+                    username: username.clone(),
+                },
             )
             .await;
 
+            if let Ok(members) = state.db.get_members(room_id).await {
+                let member_ids: Vec<Uuid> = members.iter().map(|m| m.user_id).collect();
+                clear_typing(state, room_id, user_id, &username, &member_ids);
+
+                let event = ServerResp::MemberLeft {
+                    room_id: room.id,
+                    room_name: room.name.clone(),
+                    username,
+                };
+                for member_id in member_ids {
+                    let _ = send_event(state, member_id, event.clone());
+                }
+            }
+
             let _ = send_event(
                 state,
                 user_id,
@@ -249,18 +517,25 @@ pub async fn leave_room_response(state: &&AppState, user_id: Uuid, room_id: Uuid
                     room_name: room.name,
                 },
             );
+            Ok(())
         }
         _ => {
             error!("Failed to leave room: {}", room_id);
-            let _ = send_error(state, user_id, AppError::Internal);
+            Err(AppError::Internal)
         }
-    };
+    }
 }
 
 #[instrument(skip(state), fields(user_id = %user_id))]
 pub async fn update_room_response(state: &&AppState, user_id: Uuid, room_id: Uuid, name: String) {
     info!("User {} is attempting to update room {}", user_id, room_id);
-    let _ = match state.db.get_room_by_id(room_id).await {
+    let name = normalize_room_name(&name);
+    let room = match state.db.get_room_by_id(room_id).await {
+        Ok(Some(room)) if room.is_direct => {
+            warn!("Room {} is a direct message room", room_id);
+            let _ = send_error(state, user_id, AppError::DirectMessageRoom);
+            return;
+        }
         Ok(None) => {
             warn!("Room not found: {}", room_id);
             let _ = send_error(state, user_id, AppError::RoomNotFound);
@@ -271,7 +546,7 @@ pub async fn update_room_response(state: &&AppState, user_id: Uuid, room_id: Uui
             let _ = send_error(state, user_id, AppError::Internal);
             return;
         }
-        Ok(Some(_)) => {}
+        Ok(Some(room)) => room,
     };
 
     let _ = match state.db.is_admin(room_id, user_id).await {
@@ -288,6 +563,32 @@ pub async fn update_room_response(state: &&AppState, user_id: Uuid, room_id: Uui
         _ => {}
     };
 
+    if state.config.unique_room_names {
+        let _ = match state
+            .db
+            .creator_has_room_named(room.creator_id, &name, Some(room_id))
+            .await
+        {
+            Ok(true) => {
+                warn!(
+                    "User {} already created another room named {}",
+                    room.creator_id, name
+                );
+                let _ = send_error(state, user_id, AppError::InvalidRoomName);
+                return;
+            }
+            Err(e) => {
+                error!(
+                    "Failed to check room names of user {}: {:?}",
+                    room.creator_id, e
+                );
+                let _ = send_error(state, user_id, AppError::Internal);
+                return;
+            }
+            Ok(false) => {}
+        };
+    }
+
     let _ = match state.db.update_room_name(room_id, &name).await {
         Ok(Some(room)) => {
             info!("User {} updated room {}", user_id, room_id);
@@ -312,8 +613,29 @@ pub async fn update_room_response(state: &&AppState, user_id: Uuid, room_id: Uui
 }
 
 #[instrument(skip(state), fields(user_id = %user_id))]
-pub async fn delete_room_response(state: &&AppState, user_id: Uuid, room_id: Uuid) {
-    info!("User {} is attempting to delete room {}", user_id, room_id);
+pub async fn set_room_description_response(
+    state: &&AppState,
+    user_id: Uuid,
+    room_id: Uuid,
+    description: Option<String>,
+) {
+    info!(
+        "User {} is attempting to set the description of room {}",
+        user_id, room_id
+    );
+    // Blank descriptions clear the field
+    let description = description
+        .map(|d| d.trim().to_string())
+        .filter(|d| !d.is_empty());
+
+    if let Some(description) = &description {
+        let errors = validate_room_description(description);
+        if !errors.is_empty() {
+            let _ = send_error(state, user_id, AppError::Validation(errors));
+            return;
+        }
+    }
+
     let _ = match state.db.get_room_by_id(room_id).await {
         Ok(None) => {
             warn!("Room not found: {}", room_id);
@@ -342,12 +664,101 @@ pub async fn delete_room_response(state: &&AppState, user_id: Uuid, room_id: Uui
         _ => {}
     };
 
-    let _ = match state.db.delete_room(room_id).await {
+    let _ = match state
+        .db
+        .update_room_description(room_id, description.as_deref())
+        .await
+    {
         Ok(Some(room)) => {
-            info!("User {} deleted room {}", user_id, room_id);
-            let event = ServerResp::RoomDeleted {
+            info!("User {} updated description of room {}", user_id, room_id);
+            let event = ServerResp::RoomDescriptionUpdated {
+                room_id: room.id,
+                room_name: room.name.clone(),
+                description: room.description.clone(),
+            };
+            if let Ok(members) = state.db.get_members(room_id).await {
+                for member in members {
+                    let _ = send_event(state, member.user_id, event.clone());
+                }
+            } else {
+                error!("Failed to get members of room: {}", room_id);
+                let _ = send_error(state, user_id, AppError::Internal);
+                return;
+            }
+        }
+        _ => {
+            let _ = send_error(state, user_id, AppError::Internal);
+        }
+    };
+}
+
+#[instrument(skip(state), fields(user_id = %user_id))]
+pub async fn set_room_avatar_response(
+    state: &&AppState,
+    user_id: Uuid,
+    room_id: Uuid,
+    file_id: Option<Uuid>,
+) {
+    info!(
+        "User {} is attempting to set the avatar of room {}",
+        user_id, room_id
+    );
+    let _ = match state.db.get_room_by_id(room_id).await {
+        Ok(None) => {
+            warn!("Room not found: {}", room_id);
+            let _ = send_error(state, user_id, AppError::RoomNotFound);
+            return;
+        }
+        Err(e) => {
+            error!("Failed to get room by id: {}: {:?}", room_id, e);
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+        Ok(Some(_)) => {}
+    };
+
+    let _ = match state.db.is_admin(room_id, user_id).await {
+        Ok(false) => {
+            warn!("User {} is not an admin of room {}", user_id, room_id);
+            let _ = send_error(state, user_id, AppError::NotRoomAdmin);
+            return;
+        }
+        Err(e) => {
+            error!("Failed to check if user is an admin of room: {:?}", e);
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+        _ => {}
+    };
+
+    if let Some(file_id) = file_id {
+        let _ = match state.db.get_file(file_id).await {
+            Ok(None) => {
+                warn!("File not found: {}", file_id);
+                let _ = send_error(state, user_id, AppError::FileNotFound);
+                return;
+            }
+            Ok(Some(file)) if file.uploader_id != Some(user_id) => {
+                warn!("User {} did not upload file {}", user_id, file_id);
+                let _ = send_error(state, user_id, AppError::NotFileOwner);
+                return;
+            }
+            Err(e) => {
+                error!("Failed to get file by id: {}: {:?}", file_id, e);
+                let _ = send_error(state, user_id, AppError::Internal);
+                return;
+            }
+            Ok(Some(_)) => {}
+        };
+    }
+
+    let _ = match state.db.update_room_avatar(room_id, file_id).await {
+        Ok(Some(room)) => {
+            info!("User {} updated avatar of room {}", user_id, room_id);
+            let event = ServerResp::RoomAvatarUpdated {
                 room_id: room.id,
                 room_name: room.name.clone(),
+                avatar_file_id: room.avatar_file_id,
             };
             if let Ok(members) = state.db.get_members(room_id).await {
                 for member in members {
@@ -359,6 +770,64 @@ pub async fn delete_room_response(state: &&AppState, user_id: Uuid, room_id: Uui
                 return;
             }
         }
+        _ => {
+            let _ = send_error(state, user_id, AppError::Internal);
+        }
+    };
+}
+
+#[instrument(skip(state), fields(user_id = %user_id))]
+pub async fn delete_room_response(state: &&AppState, user_id: Uuid, room_id: Uuid) {
+    info!("User {} is attempting to delete room {}", user_id, room_id);
+    let _ = match state.db.get_room_by_id(room_id).await {
+        Ok(None) => {
+            warn!("Room not found: {}", room_id);
+            let _ = send_error(state, user_id, AppError::RoomNotFound);
+            return;
+        }
+        Err(e) => {
+            error!("Failed to get room by id: {}: {:?}", room_id, e);
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+        Ok(Some(_)) => {}
+    };
+
+    let _ = match state.db.is_admin(room_id, user_id).await {
+        Ok(false) => {
+            warn!("User {} is not an admin of room {}", user_id, room_id);
+            let _ = send_error(state, user_id, AppError::NotRoomAdmin);
+            return;
+        }
+        Err(e) => {
+            error!("Failed to check if user is an admin of room: {:?}", e);
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+        _ => {}
+    };
+
+    // Memberships go with the room, so collect the recipients first
+    let member_ids = match state.db.get_member_ids(room_id).await {
+        Ok(member_ids) => member_ids,
+        Err(e) => {
+            error!("Failed to get members of room: {}: {:?}", room_id, e);
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+    };
+
+    let _ = match state.db.delete_room(room_id).await {
+        Ok(Some(room)) => {
+            info!("User {} deleted room {}", user_id, room_id);
+            let event = ServerResp::RoomDeleted {
+                room_id: room.id,
+                room_name: room.name.clone(),
+            };
+            for member_id in member_ids {
+                let _ = send_event(state, member_id, event.clone());
+            }
+        }
         _ => {
             error!("Failed to delete room: {}", room_id);
             let _ = send_error(state, user_id, AppError::Internal);
@@ -366,6 +835,111 @@ pub async fn delete_room_response(state: &&AppState, user_id: Uuid, room_id: Uui
     };
 }
 
+#[instrument(skip(state), fields(user_id = %user_id))]
+pub async fn transfer_admin_response(
+    state: &&AppState,
+    user_id: Uuid,
+    room_id: Uuid,
+    username: String,
+) {
+    info!(
+        "User {} is attempting to make {} admin of room {}",
+        user_id, username, room_id
+    );
+    let _ = match state.db.get_room_by_id(room_id).await {
+        Ok(Some(room)) if room.is_direct => {
+            warn!("Room {} is a direct message room", room_id);
+            let _ = send_error(state, user_id, AppError::DirectMessageRoom);
+            return;
+        }
+        Ok(None) => {
+            warn!("Room not found: {}", room_id);
+            let _ = send_error(state, user_id, AppError::RoomNotFound);
+            return;
+        }
+        Err(e) => {
+            error!("Failed to get room by id: {}: {:?}", room_id, e);
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+        Ok(Some(_)) => {}
+    };
+
+    let _ = match state.db.is_admin(room_id, user_id).await {
+        Ok(false) => {
+            warn!("User {} is not an admin of room {}", user_id, room_id);
+            let _ = send_error(state, user_id, AppError::NotRoomAdmin);
+            return;
+        }
+        Err(e) => {
+            error!("Failed to check if user is an admin of room: {:?}", e);
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+        _ => {}
+    };
+
+    let members = match state.db.get_members(room_id).await {
+        Ok(members) => members,
+        Err(e) => {
+            error!("Failed to get members of room: {}: {:?}", room_id, e);
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+    };
+
+    let new_admin_id = match members.iter().find(|m| m.username == username) {
+        Some(member) => member.user_id,
+        None => {
+            warn!("User {} is not a member of room {}", username, room_id);
+            let _ = send_error(state, user_id, AppError::TargetNotRoomMember);
+            return;
+        }
+    };
+
+    let old_admin_username = match members.iter().find(|m| m.user_id == user_id) {
+        Some(member) => member.username.clone(),
+        None => {
+            error!("Admin user not found in members for room {}", room_id);
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+    };
+
+    let room = match state.db.set_admin(room_id, new_admin_id).await {
+        Ok(Some(room)) => room,
+        _ => {
+            error!("Failed to set admin of room: {}", room_id);
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+    };
+    info!(
+        "User {} made {} admin of room {}",
+        user_id, room.admin_username, room_id
+    );
+
+    let _ = create_and_broadcast_system_message(
+        state,
+        room_id,
+        room.name.clone(),
+        SystemMessageContent::AdminTransferred {
+            username: room.admin_username.clone(),
+            by: old_admin_username,
+        },
+    )
+    .await;
+
+    let event = ServerResp::AdminTransferred {
+        room_id: room.id,
+        room_name: room.name,
+        new_admin_username: room.admin_username,
+    };
+    for member in members {
+        let _ = send_event(state, member.user_id, event.clone());
+    }
+}
+
 #[instrument(skip(state), fields(user_id = %user_id))]
 pub async fn get_room_info_response(state: &&AppState, user_id: Uuid, room_id: Uuid) {
     info!("User {} is requesting info for room {}", user_id, room_id);
@@ -383,7 +957,11 @@ pub async fn get_room_info_response(state: &&AppState, user_id: Uuid, room_id: U
         }
     };
 
-    let members = match state.db.get_members(room_id).await {
+    let members = match state
+        .db
+        .get_members_paginated(room_id, ROOM_INFO_MEMBER_LIMIT, 0)
+        .await
+    {
         Ok(members) => members,
         Err(e) => {
             error!("Failed to get members of room: {}: {:?}", room_id, e);
@@ -392,14 +970,10 @@ pub async fn get_room_info_response(state: &&AppState, user_id: Uuid, room_id: U
         }
     };
 
-    let admin_username = match members
-        .iter()
-        .find(|m| m.user_id == room.admin_id)
-        .map(|m| m.username.clone())
-    {
-        Some(username) => username,
-        None => {
-            error!("Admin user not found in members for room: {}", room_id);
+    let member_count = match state.db.count_members(room_id).await {
+        Ok(count) => count,
+        Err(e) => {
+            error!("Failed to count members of room: {}: {:?}", room_id, e);
             let _ = send_error(state, user_id, AppError::Internal);
             return;
         }
@@ -414,13 +988,15 @@ pub async fn get_room_info_response(state: &&AppState, user_id: Uuid, room_id: U
         }
     };
 
-    let members_info = members
-        .into_iter()
-        .map(|m| MemberInfo {
-            username: m.username,
-            joined_at: m.joined_at,
-        })
-        .collect::<Vec<MemberInfo>>();
+    // Only worth a warning; the stored name still identifies the admin.
+    // Checked only when the page already holds every member.
+    if member_count <= ROOM_INFO_MEMBER_LIMIT && !members.iter().any(|m| m.user_id == room.admin_id)
+    {
+        warn!(
+            "Admin {} of room {} is not a member, using stored name {}",
+            room.admin_id, room_id, room.admin_username
+        );
+    }
 
     info!("Sending room info for room {}", room_id);
     let _ = send_event(
@@ -429,14 +1005,80 @@ pub async fn get_room_info_response(state: &&AppState, user_id: Uuid, room_id: U
         ServerResp::RoomInfo {
             room_id: room.id,
             room_name: room.name,
-            admin_username,
+            description: room.description,
+            avatar_file_id: room.avatar_file_id,
+            // Kept in step with admin_id by `set_admin`
+            admin_username: room.admin_username,
             creator_username,
-            members: members_info,
+            members: members.into_iter().map(MemberInfo::from).collect(),
+            member_count,
             created_at: room.created_at,
+            message_count: room.message_count,
         },
     );
 }
 
+#[instrument(skip(state), fields(user_id = %user_id))]
+pub async fn get_room_members_response(
+    state: &&AppState,
+    user_id: Uuid,
+    room_id: Uuid,
+    limit: i64,
+    offset: i64,
+) {
+    info!("User {} is requesting members of room {}", user_id, room_id);
+    if limit < 0 || offset < 0 {
+        warn!("Invalid member page: limit {} offset {}", limit, offset);
+        let _ = send_error(state, user_id, AppError::InvalidRequestFormat);
+        return;
+    }
+
+    let _ = match state.db.is_member(room_id, user_id).await {
+        Ok(false) => {
+            warn!("User {} is not a member of room {}", user_id, room_id);
+            let _ = send_error(state, user_id, AppError::NotRoomMember);
+            return;
+        }
+        Err(e) => {
+            error!(
+                "Database error checking membership for user {} in room {}: {:?}",
+                user_id, room_id, e
+            );
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+        _ => {}
+    };
+
+    let total = match state.db.count_members(room_id).await {
+        Ok(total) => total,
+        Err(e) => {
+            error!("Failed to count members of room: {}: {:?}", room_id, e);
+            let _ = send_error(state, user_id, AppError::Internal);
+            return;
+        }
+    };
+
+    let limit = limit.min(MAX_MEMBER_PAGE);
+    let _ = match state.db.get_members_paginated(room_id, limit, offset).await {
+        Ok(members) => {
+            let _ = send_event(
+                state,
+                user_id,
+                ServerResp::RoomMembers {
+                    room_id,
+                    members: members.into_iter().map(MemberInfo::from).collect(),
+                    total,
+                },
+            );
+        }
+        Err(e) => {
+            error!("Failed to get members of room: {}: {:?}", room_id, e);
+            let _ = send_error(state, user_id, AppError::Internal);
+        }
+    };
+}
+
 #[instrument(skip(state), fields(user_id = %user_id))]
 pub async fn get_rooms_info_response(state: &&AppState, user_id: Uuid) {
     info!("User {} is requesting info for all their rooms", user_id);
@@ -445,19 +1087,14 @@ pub async fn get_rooms_info_response(state: &&AppState, user_id: Uuid) {
             info!("Sending rooms info to user {}", user_id);
             let rooms_info = rooms
                 .into_iter()
-                .map(|(member, last_message)| {
-                    let last_message = last_message.map(|msg| MessageInfo {
-                        message_id: msg.id,
-                        author_username: msg.author_username,
-                        content: msg.content,
-                        message_type: msg.message_type,
-                        message_status: msg.status,
-                        created_at: msg.created_at,
-                    });
+                .map(|(member, room, last_message)| {
+                    let last_message = last_message.map(MessageInfo::from);
 
                     RoomInfo {
                         room_id: member.room_id,
                         room_name: member.room_name,
+                        description: room.as_ref().and_then(|r| r.description.clone()),
+                        avatar_file_id: room.and_then(|r| r.avatar_file_id),
                         last_message,
                         unread_count: member.unread_count,
                     }
@@ -472,3 +1109,24 @@ pub async fn get_rooms_info_response(state: &&AppState, user_id: Uuid) {
         }
     };
 }
+
+#[instrument(skip(state), fields(user_id = %user_id))]
+pub async fn get_rooms_summary_response(state: &&AppState, user_id: Uuid) {
+    info!("User {} is requesting a summary of their rooms", user_id);
+    let _ = match state.db.get_rooms_summary_for_user(user_id).await {
+        Ok(members) => {
+            let rooms = members
+                .into_iter()
+                .map(|member| RoomSummary {
+                    room_id: member.room_id,
+                    unread_count: member.unread_count,
+                })
+                .collect::<Vec<RoomSummary>>();
+            let _ = send_event(state, user_id, ServerResp::RoomsSummary { rooms });
+        }
+        Err(e) => {
+            error!("Failed to get room summary for user {}: {:?}", user_id, e);
+            let _ = send_error(state, user_id, AppError::Internal);
+        }
+    };
+}
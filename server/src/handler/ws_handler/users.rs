@@ -8,10 +8,16 @@ use crate::{
     errors::error::AppError,
 };
 
+use super::messages::clear_typing;
 use crate::handler::ws_handler::utils::{
-    create_and_broadcast_system_message, send_error, send_event,
+    CLOSE_NORMAL, close_connection, create_and_broadcast_system_message, send_error, send_event,
 };
 
+const MIN_USER_SEARCH_LEN: usize = 2;
+
+/// Upper bound on a `SearchUsers` page, whatever limit is asked for
+const MAX_USER_SEARCH_PAGE: i64 = 50;
+
 #[instrument(skip(state), fields(user_id = %user_id))]
 pub async fn delete_account_response(state: &&AppState, user_id: Uuid) {
     info!("User {} is attempting to delete their account", user_id);
@@ -23,6 +29,8 @@ pub async fn delete_account_response(state: &&AppState, user_id: Uuid) {
                 user_id,
                 ServerResp::AccountDeleted { user_id: user.id },
             );
+            // The socket would only fail every request from here on
+            close_connection(state, user_id, CLOSE_NORMAL, "account_deleted");
         }
         _ => {
             error!("Failed to delete user account for user {}", user_id);
@@ -42,8 +50,13 @@ pub async fn kick_member_response(
         "User {} is attempting to kick {} from room {}",
         user_id, username, room_id
     );
-    let room_name = match state.db.get_room_by_id(room_id).await {
-        Ok(Some(room)) => room.name,
+    let _ = match state.db.get_room_by_id(room_id).await {
+        Ok(Some(room)) if room.is_direct => {
+            warn!("Room {} is a direct message room", room_id);
+            let _ = send_error(state, user_id, AppError::DirectMessageRoom);
+            return;
+        }
+        Ok(Some(_)) => {}
         Ok(None) => {
             warn!("Room not found: {}", room_id);
             let _ = send_error(state, user_id, AppError::RoomNotFound);
@@ -119,30 +132,37 @@ pub async fn kick_member_response(
                     author_username: message.author_username,
                     content: message.content,
                     message_type: message.message_type,
+                    file_id: message.file_id,
                     created_at: message.created_at,
+                    reply_to: message.reply_to,
                 };
                 let _ = send_event(state, member.user_id, event);
             }
 
+            // The kicked user gets the same event as the remaining members
+            let event = ServerResp::MemberKicked {
+                room_id: member.room_id,
+                room_name: member.room_name.clone(),
+                username: member.username.clone(),
+            };
             if let Ok(members) = state.db.get_members(room_id).await {
-                let event = ServerResp::MemberKicked {
-                    room_id: member.room_id,
-                    room_name: member.room_name,
-                    username: member.username,
-                };
-                for m in members {
-                    let _ = send_event(state, m.user_id, event.clone());
+                let member_ids: Vec<Uuid> = members.iter().map(|m| m.user_id).collect();
+                clear_typing(
+                    state,
+                    room_id,
+                    member.user_id,
+                    &member.username,
+                    &member_ids,
+                );
+
+                for member_id in member_ids {
+                    let _ = send_event(state, member_id, event.clone());
                 }
             } else {
                 error!("Database error getting members for room {}", room_id);
                 let _ = send_error(state, user_id, AppError::Internal);
                 return;
             }
-            let event = ServerResp::MemberKicked {
-                room_id,
-                room_name,
-                username: username.clone(),
-            };
             let _ = send_event(state, member.user_id, event);
         }
         Ok(None) => {
@@ -165,13 +185,70 @@ pub async fn kick_member_response(
 }
 
 #[instrument(skip(state), fields(user_id = %user_id))]
-pub async fn search_users_response(state: &&AppState, user_id: Uuid, query: String) {
+pub async fn search_users_response(
+    state: &&AppState,
+    user_id: Uuid,
+    query: String,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    exclude_room_id: Option<Uuid>,
+) {
     info!(
         "User {} is searching for users with query '{}'",
         user_id, query
     );
-    let _ = match state.db.search_users(&query).await {
-        Ok(users) => {
+    // Shorter queries match nearly everyone and scan the whole table, longer
+    // ones only make for expensive patterns. Surrounding whitespace can't be
+    // part of a username, so it doesn't count towards the minimum.
+    let query = query.trim();
+    let query_len = query.chars().count();
+    if query_len < MIN_USER_SEARCH_LEN || query_len > state.config.max_search_query_len {
+        warn!("User search query has invalid length: '{}'", query);
+        let _ = send_error(state, user_id, AppError::InvalidRequestFormat);
+        return;
+    }
+
+    let limit = limit.unwrap_or(state.config.user_search_limit);
+    let offset = offset.unwrap_or(0);
+    if limit < 0 || offset < 0 {
+        warn!(
+            "Invalid user search page: limit {} offset {}",
+            limit, offset
+        );
+        let _ = send_error(state, user_id, AppError::InvalidRequestFormat);
+        return;
+    }
+    let limit = limit.min(MAX_USER_SEARCH_PAGE);
+
+    // Otherwise anyone could probe who belongs to a room
+    if let Some(room_id) = exclude_room_id {
+        let _ = match state.db.is_member(room_id, user_id).await {
+            Ok(false) => {
+                warn!("User {} is not a member of room {}", user_id, room_id);
+                let _ = send_error(state, user_id, AppError::NotRoomMember);
+                return;
+            }
+            Err(e) => {
+                error!(
+                    "Database error checking membership for user {} in room {}: {:?}",
+                    user_id, room_id, e
+                );
+                let _ = send_error(state, user_id, AppError::Internal);
+                return;
+            }
+            _ => {}
+        };
+    }
+
+    // One extra row tells whether another page follows
+    let _ = match state
+        .db
+        .search_users(query, user_id, exclude_room_id, limit + 1, offset)
+        .await
+    {
+        Ok(mut users) => {
+            let has_more = users.len() as i64 > limit;
+            users.truncate(limit as usize);
             info!(
                 "Found {} users matching query '{}' for user {}",
                 users.len(),
@@ -182,10 +259,18 @@ pub async fn search_users_response(state: &&AppState, user_id: Uuid, query: Stri
                 .into_iter()
                 .map(|u| UserInfo {
                     username: u.username,
+                    avatar_file_id: u.avatar_file_id,
                     created_at: u.created_at,
                 })
                 .collect::<Vec<UserInfo>>();
-            let _ = send_event(state, user_id, ServerResp::UsersFound { users: user_infos });
+            let _ = send_event(
+                state,
+                user_id,
+                ServerResp::UsersFound {
+                    users: user_infos,
+                    has_more,
+                },
+            );
         }
         Err(e) => {
             error!(
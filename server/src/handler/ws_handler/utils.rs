@@ -1,18 +1,162 @@
-use crate::config::AppState;
+use crate::config::{AppState, Connection, ConnectionOverflow, WsOutbound};
 use crate::database::{
     models::MessageType, room_members::RoomMemberRepository, user_messages::MessageRepository,
 };
 use crate::dtos::{ServerResp, SystemMessageContent};
 use crate::errors::error::AppError;
-use tracing::{debug, error};
+use std::sync::atomic::Ordering;
+use tracing::{debug, error, info};
 use uuid::Uuid;
 
+/// Close code for sockets ended on purpose, as in RFC 6455 "normal closure"
+pub const CLOSE_NORMAL: u16 = 1000;
+
+/// Close code for sockets dropped because the server is going away
+pub const CLOSE_GOING_AWAY: u16 = 1001;
+
+/// Close code for sockets ended by moderation, an expired token or idle eviction, as in RFC 6455 "policy violation"
+pub const CLOSE_POLICY: u16 = 1008;
+
+/// Close code for sockets refused because the server is full, as in RFC 6455
+/// "try again later"
+pub const CLOSE_TRY_AGAIN_LATER: u16 = 1013;
+
+/// Sends to every open socket of the user
 pub fn send_event(state: &AppState, user_id: Uuid, event: ServerResp) {
-    if let Some(sender) = state.channels.get(&user_id) {
-        sender.send(event).ok();
+    if let Some(connections) = state.channels.get(&user_id) {
+        for connection in connections.iter() {
+            connection.tx.send(WsOutbound::Event(event.clone())).ok();
+        }
+    }
+}
+
+/// Sends to one socket only, for replies about that socket itself
+pub fn send_event_to_connection(
+    state: &AppState,
+    user_id: Uuid,
+    connection_id: Uuid,
+    event: ServerResp,
+) {
+    if let Some(connections) = state.channels.get(&user_id)
+        && let Some(connection) = connections.iter().find(|c| c.id == connection_id)
+    {
+        connection.tx.send(WsOutbound::Event(event)).ok();
     }
 }
 
+/// Closes all of the user's sockets with a close frame; returns whether any
+/// were open
+pub fn close_connection(state: &AppState, user_id: Uuid, code: u16, reason: &'static str) -> bool {
+    match state.channels.remove(&user_id) {
+        Some((_, connections)) => {
+            state
+                .open_connections
+                .fetch_sub(connections.len(), Ordering::SeqCst);
+            for connection in connections {
+                send_close(user_id, connection, code, reason);
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+/// Like `close_connection`, but leaves the user's other sockets open
+pub fn close_connection_by_id(
+    state: &AppState,
+    user_id: Uuid,
+    connection_id: Uuid,
+    code: u16,
+    reason: &'static str,
+) -> bool {
+    match remove_connection(state, user_id, connection_id) {
+        Some(connection) => {
+            send_close(user_id, connection, code, reason);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Closes every open socket, returning how many users were connected
+pub fn close_all_connections(state: &AppState, code: u16, reason: &'static str) -> usize {
+    // Collected first, as removing while iterating would deadlock the map
+    let user_ids: Vec<Uuid> = state.channels.iter().map(|entry| *entry.key()).collect();
+    user_ids
+        .into_iter()
+        .filter(|user_id| close_connection(state, *user_id, code, reason))
+        .count()
+}
+
+/// Counts one more socket under `max_connections`, evicting the longest idle
+/// one if configured to; `false` if the new one must be refused. An admitted
+/// socket must be added to `channels`, as removing it gives its slot back.
+pub fn admit_connection(state: &AppState) -> bool {
+    let max = state.config.max_connections;
+    loop {
+        let reserved = state
+            .open_connections
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |open| {
+                (open < max).then_some(open + 1)
+            })
+            .is_ok();
+        if reserved {
+            return true;
+        }
+        if state.config.connection_overflow == ConnectionOverflow::Reject {
+            return false;
+        }
+
+        // Found first, as closing while iterating would deadlock the map
+        let idlest = state
+            .channels
+            .iter()
+            .flat_map(|entry| {
+                let user_id = *entry.key();
+                entry
+                    .iter()
+                    .map(|c| (user_id, c.id, *c.last_active.lock().unwrap()))
+                    .collect::<Vec<_>>()
+            })
+            .min_by_key(|(_, _, last_active)| *last_active);
+        let Some((user_id, connection_id, _)) = idlest else {
+            return false;
+        };
+        // The freed slot is reserved like any other on the next pass, since a
+        // racing admission may evict the same socket or take its slot first
+        close_connection_by_id(state, user_id, connection_id, CLOSE_POLICY, "evicted_idle");
+    }
+}
+
+/// Takes one socket out of the user's entry, dropping the entry once empty.
+/// Dropping the returned connection ends its send task.
+pub fn remove_connection(
+    state: &AppState,
+    user_id: Uuid,
+    connection_id: Uuid,
+) -> Option<Connection> {
+    let removed = match state.channels.get_mut(&user_id) {
+        Some(mut connections) => {
+            let index = connections.iter().position(|c| c.id == connection_id)?;
+            state.open_connections.fetch_sub(1, Ordering::SeqCst);
+            Some(connections.remove(index))
+        }
+        None => None,
+    };
+    state
+        .channels
+        .remove_if(&user_id, |_, connections| connections.is_empty());
+    removed
+}
+
+fn send_close(user_id: Uuid, connection: Connection, code: u16, reason: &'static str) {
+    info!(
+        "Closing connection {} of user {}: {}",
+        connection.id, user_id, reason
+    );
+    connection.tx.send(WsOutbound::Close { code, reason }).ok();
+}
+
 pub fn send_error(state: &AppState, user_id: Uuid, error: AppError) {
     send_event(
         state,
@@ -45,11 +189,13 @@ pub async fn create_and_broadcast_system_message(
             None,
             &content_str,
             MessageType::System,
+            None,
+            None,
         )
         .await
     {
         Ok(message) => {
-            if let Ok(members) = state.db.get_members(room_id).await {
+            if let Ok(member_ids) = state.db.get_member_ids(room_id).await {
                 let event = ServerResp::MessageReceived {
                     message_id: message.id,
                     room_id: message.room_id,
@@ -57,11 +203,13 @@ pub async fn create_and_broadcast_system_message(
                     author_username: message.author_username.clone(),
                     content: message.content.clone(),
                     message_type: message.message_type,
+                    file_id: message.file_id,
                     created_at: message.created_at,
+                    reply_to: message.reply_to,
                 };
                 debug!("Broadcasting system message event: {:?}", event);
-                for member in members {
-                    let _ = send_event(state, member.user_id, event.clone());
+                for member_id in member_ids {
+                    let _ = send_event(state, member_id, event.clone());
                 }
             }
             Ok(message)
@@ -1,62 +1,185 @@
 use axum::{
     extract::{
         Query, State,
-        ws::{Message, WebSocket, WebSocketUpgrade},
+        ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade},
     },
+    http::{HeaderMap, header},
     response::IntoResponse,
 };
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use futures::{sink::SinkExt, stream::StreamExt};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 use tokio::sync::mpsc;
-use tracing::{error, info, instrument};
+use tracing::{info, instrument, warn};
 use uuid::Uuid;
 
 use crate::{
-    config::AppState,
-    dtos::{ClientReq, ServerResp, WsParams},
+    config::{AppState, Connection, WsOutbound},
+    database::users::UserRepository,
+    dtos::{ClientReq, ServerEnvelope, ServerResp, WsParams, supported_subprotocols},
     errors::error::AppError,
-    utils::token::verify_access_token,
+    utils::{rate_limit::TokenBucket, token::verify_access_token},
 };
 
 use super::{
+    drafts::*,
     invitations::*,
     messages::*,
+    pins::*,
+    reactions::*,
     rooms::*,
     users::*,
-    utils::send_error,
+    utils::{
+        CLOSE_GOING_AWAY, CLOSE_POLICY, CLOSE_TRY_AGAIN_LATER, admit_connection,
+        close_all_connections, close_connection_by_id, remove_connection, send_error, send_event,
+        send_event_to_connection,
+    },
 };
 
-#[instrument(skip(ws, state))]
+/// How long a closing socket may take to flush its close frame
+const CLOSE_FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[instrument(skip(ws, state, headers))]
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
+    headers: HeaderMap,
     Query(params): Query<WsParams>,
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, AppError> {
     info!("WS connection attempt");
+    check_origin(&state, &headers)?;
+    let ws = negotiate_subprotocol(ws)?;
+
+    // The signature is checked before anything is looked up, so a forged
+    // token gets the same `InvalidToken` whether or not its user exists
     let (user_id, _role, exp) =
         match verify_access_token(&params.token, state.config.jwt_secret.as_bytes()) {
             Ok(res) => res,
             Err(e) => {
-                error!("WS token verification failed: {:?}", e);
+                warn!("WS token verification failed: {:?}", e);
                 return Err(e);
             }
         };
-    info!("WS connection accepted for user: {}", user_id);
 
-    Ok(ws.on_upgrade(move |socket| handle_socket(socket, state, user_id, exp)))
+    // Access tokens outlive account deletion, so check the user is still there
+    if state.db.get_user_by_id(user_id).await?.is_none() {
+        warn!("WS connection rejected for deleted user: {}", user_id);
+        return Err(AppError::AccountNotFound);
+    }
+    let connection_id = Uuid::new_v4();
+    info!(
+        "WS connection {} accepted for user: {}",
+        connection_id, user_id
+    );
+
+    Ok(ws.on_upgrade(move |socket| handle_socket(socket, state, user_id, connection_id, exp)))
+}
+
+/// Browsers always send `Origin` on WebSocket upgrades, so a mismatch means the
+/// upgrade was initiated by a foreign page. Without `allowed_origins` only the
+/// server's own origin passes, judged by the `Host` the browser connected to.
+/// A missing header is let through: native and CLI clients don't send one, and
+/// a browser can't leave it out, so it can't be used to hijack a session.
+fn check_origin(state: &AppState, headers: &HeaderMap) -> Result<(), AppError> {
+    let origin = match headers.get(header::ORIGIN) {
+        Some(origin) => origin.to_str().map_err(|_| AppError::OriginNotAllowed)?,
+        None => return Ok(()),
+    };
+
+    let allowed = if state.config.allowed_origins.is_empty() {
+        let host = headers.get(header::HOST).and_then(|h| h.to_str().ok());
+        // `Origin` is `scheme://host[:port]`, `Host` is `host[:port]`
+        host.is_some() && origin.split_once("://").map(|(_, h)| h) == host
+    } else {
+        state.config.allowed_origins.iter().any(|o| o == origin)
+    };
+
+    if allowed {
+        Ok(())
+    } else {
+        warn!("WS upgrade rejected for origin {}", origin);
+        Err(AppError::OriginNotAllowed)
+    }
 }
 
-#[instrument(skip(socket, state), fields(user_id = %user_id))]
-async fn handle_socket(socket: WebSocket, state: AppState, user_id: Uuid, exp: usize) {
+/// Picks the newest `chat.v{n}` both sides speak, which the upgrade response
+/// echoes back. Clients that offer no subprotocol at all get version 1.
+fn negotiate_subprotocol(ws: WebSocketUpgrade) -> Result<WebSocketUpgrade, AppError> {
+    let requested = ws.requested_protocols().count();
+    let ws = ws.protocols(supported_subprotocols());
+    match ws.selected_protocol() {
+        Some(protocol) => info!("WS subprotocol negotiated: {:?}", protocol),
+        None if requested > 0 => {
+            warn!("WS upgrade rejected, no supported subprotocol offered");
+            return Err(AppError::UnsupportedProtocol);
+        }
+        None => {}
+    }
+    Ok(ws)
+}
+
+#[instrument(skip(socket, state), fields(user_id = %user_id, connection_id = %connection_id))]
+async fn handle_socket(
+    mut socket: WebSocket,
+    state: AppState,
+    user_id: Uuid,
+    connection_id: Uuid,
+    exp: usize,
+) {
+    if !admit_connection(&state) {
+        warn!("Refusing connection {}, server is full", connection_id);
+        let frame = CloseFrame {
+            code: CLOSE_TRY_AGAIN_LATER,
+            reason: "server_full".into(),
+        };
+        let _ = socket.send(Message::Close(Some(frame))).await;
+        return;
+    }
+
+    let connected_since = Utc::now();
     let (mut sender, mut receiver) = socket.split();
-    let (tx, mut rx) = mpsc::unbounded_channel::<ServerResp>();
+    let (tx, mut rx) = mpsc::unbounded_channel::<WsOutbound>();
+    // Weak, so a closed connection's send task still sees the channel close
+    // once its entry is gone
+    let heartbeat_tx = tx.downgrade();
+    let last_pong = Arc::new(Mutex::new(Instant::now()));
+    let last_active = Arc::new(Mutex::new(Instant::now()));
 
-    state.channels.insert(user_id, tx);
+    state.channels.entry(user_id).or_default().push(Connection {
+        id: connection_id,
+        tx,
+        last_active: last_active.clone(),
+    });
+    info!("WS connection {} opened", connection_id);
 
     let mut send_task = tokio::spawn(async move {
-        while let Some(event) = rx.recv().await {
-            if let Ok(msg) = serde_json::to_string(&event) {
-                if sender.send(Message::Text(msg.into())).await.is_err() {
+        while let Some(outbound) = rx.recv().await {
+            match outbound {
+                WsOutbound::Event(event) => {
+                    if let Ok(msg) = serde_json::to_string(&ServerEnvelope::new(&event))
+                        && sender.send(Message::Text(msg.into())).await.is_err()
+                    {
+                        break;
+                    }
+                }
+                WsOutbound::Ping => {
+                    if sender
+                        .send(Message::Ping(Default::default()))
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+                WsOutbound::Close { code, reason } => {
+                    let frame = CloseFrame {
+                        code,
+                        reason: reason.into(),
+                    };
+                    let _ = sender.send(Message::Close(Some(frame))).await;
                     break;
                 }
             }
@@ -64,22 +187,46 @@ async fn handle_socket(socket: WebSocket, state: AppState, user_id: Uuid, exp: u
     });
 
     let state_clone = state.clone();
+    let pong_seen = last_pong.clone();
+    let mut rate_limit = TokenBucket::new(
+        state.config.ws_rate_limit,
+        Duration::from_secs(state.config.ws_rate_window_secs),
+    );
     let mut recv_task = tokio::spawn(async move {
         while let Some(Ok(msg)) = receiver.next().await {
             match msg {
-                Message::Text(text) => match serde_json::from_str::<ClientReq>(&text) {
+                Message::Text(_) if !rate_limit.try_acquire() => {
+                    warn!("Rate limiting connection {}", connection_id);
+                    let _ = send_error(&state_clone, user_id, AppError::RateLimited);
+                }
+                Message::Text(text) => match parse_client_req(&text) {
                     Ok(event) => {
-                        handle_event(event, &state_clone, user_id).await;
+                        *last_active.lock().unwrap() = Instant::now();
+                        let kind = event.kind();
+                        let started = Instant::now();
+                        handle_event(
+                            event,
+                            &state_clone,
+                            user_id,
+                            connection_id,
+                            connected_since,
+                            exp,
+                        )
+                        .await;
+                        state_clone.metrics.record_event(kind, started.elapsed());
                     }
-                    Err(_) => {
-                        let _ = send_error(&state_clone, user_id, AppError::InvalidRequestFormat);
+                    Err(e) => {
+                        let _ = send_error(&state_clone, user_id, e);
                     }
                 },
                 Message::Binary(_) => {}
                 Message::Close(_) => {
                     break;
                 }
-                Message::Ping(_) | Message::Pong(_) => {}
+                Message::Pong(_) => {
+                    *pong_seen.lock().unwrap() = Instant::now();
+                }
+                Message::Ping(_) => {}
             }
         }
     });
@@ -95,41 +242,138 @@ async fn handle_socket(socket: WebSocket, state: AppState, user_id: Uuid, exp: u
         tokio::time::sleep(duration_until_exp).await;
     });
 
+    let ping_interval = Duration::from_secs(state.config.ws_ping_interval_secs);
+    let pong_timeout = Duration::from_secs(state.config.ws_pong_timeout_secs);
+    let mut heartbeat_task = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(ping_interval);
+        loop {
+            interval.tick().await;
+            if last_pong.lock().unwrap().elapsed() > pong_timeout {
+                warn!(
+                    "No pong from connection {} in {:?}",
+                    connection_id, pong_timeout
+                );
+                break;
+            }
+            match heartbeat_tx.upgrade() {
+                Some(tx) if tx.send(WsOutbound::Ping).is_ok() => {}
+                _ => break,
+            }
+        }
+    });
+
     tokio::select! {
         _ = (&mut send_task) => {
             recv_task.abort();
             expiration_task.abort();
+            heartbeat_task.abort();
         },
         _ = (&mut recv_task) => {
             send_task.abort();
             expiration_task.abort();
+            heartbeat_task.abort();
+        },
+        _ = (&mut heartbeat_task) => {
+            info!("Dropping unresponsive connection {}", connection_id);
+            send_task.abort();
+            recv_task.abort();
+            expiration_task.abort();
         },
         _ = (&mut expiration_task) => {
             info!("Closing connection for user {} due to token expiration", user_id);
-            send_task.abort();
             recv_task.abort();
+            heartbeat_task.abort();
+            close_connection_by_id(&state, user_id, connection_id, CLOSE_POLICY, "token_expired");
+            // Give the close frame a moment to reach the client
+            if tokio::time::timeout(CLOSE_FLUSH_TIMEOUT, &mut send_task).await.is_err() {
+                send_task.abort();
+            }
         }
     };
 
-    state.channels.remove(&user_id);
+    // Already gone if this socket was closed on purpose
+    remove_connection(&state, user_id, connection_id);
+    if !state.channels.contains_key(&user_id) {
+        clear_typing_on_disconnect(&state, user_id).await;
+    }
+    info!("WS connection {} closed", connection_id);
+}
+
+/// Tells every connected client the server is going away, so they can
+/// reconnect elsewhere instead of waiting on a dead socket
+pub fn close_connections_for_shutdown(state: &AppState) {
+    // Queued ahead of the close frame on each socket
+    let user_ids: Vec<Uuid> = state.channels.iter().map(|entry| *entry.key()).collect();
+    for user_id in user_ids {
+        send_event(state, user_id, ServerResp::ServerShutdown);
+    }
+    let closed = close_all_connections(state, CLOSE_GOING_AWAY, "server_shutdown");
+    info!("Closed {} WebSocket connections for shutdown", closed);
+}
+
+/// Unknown `type`s get their own error so newer clients can tell them apart
+/// from malformed requests, which carry the parser's reason
+fn parse_client_req(text: &str) -> Result<ClientReq, AppError> {
+    match serde_json::from_str::<ClientReq>(text) {
+        Ok(ClientReq::Unknown) => {
+            let request_type = serde_json::from_str::<serde_json::Value>(text)
+                .ok()
+                .and_then(|v| v["type"].as_str().map(str::to_string))
+                .unwrap_or_default();
+            warn!("Unknown request type: {}", request_type);
+            Err(AppError::UnknownRequestType(request_type))
+        }
+        Ok(event) => Ok(event),
+        Err(e) => Err(AppError::InvalidRequestFormatDetailed(e.to_string())),
+    }
 }
 
-#[instrument(skip(state), fields(user_id = %user_id))]
-async fn handle_event(event: ClientReq, state: &AppState, user_id: Uuid) {
+#[instrument(skip(state), fields(user_id = %user_id, connection_id = %connection_id))]
+async fn handle_event(
+    event: ClientReq,
+    state: &AppState,
+    user_id: Uuid,
+    connection_id: Uuid,
+    connected_since: DateTime<Utc>,
+    exp: usize,
+) {
     match event {
         ClientReq::CreateRoom { name } => create_room_response(&state, user_id, name).await,
+        ClientReq::StartDirectMessage { username } => {
+            start_direct_message_response(&state, user_id, username).await
+        }
         ClientReq::JoinRoom { invitation_id } => {
             join_room_response(&state, user_id, invitation_id).await
         }
+        ClientReq::AcceptInvitations { invitation_ids } => {
+            accept_invitations_response(&state, user_id, invitation_ids).await
+        }
         ClientReq::LeaveRoom { room_id } => leave_room_response(&state, user_id, room_id).await,
+        ClientReq::LeaveRooms { room_ids } => leave_rooms_response(&state, user_id, room_ids).await,
         ClientReq::UpdateRoom { room_id, name } => {
             update_room_response(&state, user_id, room_id, name).await
         }
+        ClientReq::SetRoomDescription {
+            room_id,
+            description,
+        } => set_room_description_response(&state, user_id, room_id, description).await,
+        ClientReq::SetRoomAvatar { room_id, file_id } => {
+            set_room_avatar_response(&state, user_id, room_id, file_id).await
+        }
         ClientReq::DeleteRoom { room_id } => delete_room_response(&state, user_id, room_id).await,
+        ClientReq::TransferAdmin { room_id, username } => {
+            transfer_admin_response(&state, user_id, room_id, username).await
+        }
         ClientReq::GetRoomInfo { room_id } => {
             get_room_info_response(&state, user_id, room_id).await
         }
+        ClientReq::GetRoomMembers {
+            room_id,
+            limit,
+            offset,
+        } => get_room_members_response(&state, user_id, room_id, limit, offset).await,
         ClientReq::GetRoomsInfo => get_rooms_info_response(&state, user_id).await,
+        ClientReq::GetRoomsSummary => get_rooms_summary_response(&state, user_id).await,
         ClientReq::Invite { room_id, username } => {
             invite_response(&state, user_id, room_id, username).await
         }
@@ -137,27 +381,120 @@ async fn handle_event(event: ClientReq, state: &AppState, user_id: Uuid) {
             decline_invitation_response(&state, user_id, invitation_id).await
         }
         ClientReq::GetPendingInvitations => get_pending_invitations_response(&state, user_id).await,
+        ClientReq::GetPendingInvitationCount => {
+            get_pending_invitation_count_response(&state, user_id).await
+        }
+        ClientReq::GetRoomInvitations { room_id } => {
+            get_room_invitations_response(&state, user_id, room_id).await
+        }
+        ClientReq::PreviewRoom { invitation_id } => {
+            preview_room_response(&state, user_id, invitation_id).await
+        }
         ClientReq::SendMessage {
             room_id,
             content,
             message_type,
-        } => send_message_response(&state, user_id, room_id, content, message_type).await,
+            file_id,
+            reply_to,
+        } => {
+            send_message_response(
+                &state,
+                user_id,
+                room_id,
+                content,
+                message_type,
+                file_id,
+                reply_to,
+            )
+            .await
+        }
         ClientReq::EditMessage {
             message_id,
             new_content,
         } => edit_message_response(&state, user_id, message_id, new_content).await,
+        ClientReq::Typing { room_id, is_typing } => {
+            typing_response(&state, user_id, room_id, is_typing).await
+        }
         ClientReq::DeleteMessage { message_id } => {
             delete_message_response(&state, user_id, message_id).await
         }
+        ClientReq::React { message_id, emoji } => {
+            react_response(&state, user_id, message_id, emoji).await
+        }
+        ClientReq::Unreact { message_id, emoji } => {
+            unreact_response(&state, user_id, message_id, emoji).await
+        }
+        ClientReq::PinMessage { message_id } => {
+            pin_message_response(&state, user_id, message_id).await
+        }
+        ClientReq::UnpinMessage { message_id } => {
+            unpin_message_response(&state, user_id, message_id).await
+        }
+        ClientReq::GetPinnedMessages { room_id } => {
+            get_pinned_messages_response(&state, user_id, room_id).await
+        }
         ClientReq::GetMessages {
             room_id,
             limit,
             offset,
         } => get_messages_response(&state, user_id, room_id, limit, offset).await,
+        ClientReq::GetMessagesBefore {
+            room_id,
+            before,
+            limit,
+        } => get_messages_before_response(&state, user_id, room_id, before, limit).await,
+        ClientReq::GetThread {
+            root_message_id,
+            limit,
+            offset,
+        } => get_thread_response(&state, user_id, root_message_id, limit, offset).await,
         ClientReq::DeleteAccount => delete_account_response(&state, user_id).await,
         ClientReq::KickMember { room_id, username } => {
             kick_member_response(&state, user_id, room_id, username).await
         }
-        ClientReq::SearchUsers { query } => search_users_response(&state, user_id, query).await,
+        ClientReq::SearchUsers {
+            query,
+            limit,
+            offset,
+            exclude_room_id,
+        } => search_users_response(&state, user_id, query, limit, offset, exclude_room_id).await,
+        ClientReq::SearchAllMessages { query, limit } => {
+            search_all_messages_response(&state, user_id, query, limit).await
+        }
+        ClientReq::SearchMessages {
+            room_id,
+            query,
+            limit,
+        } => search_messages_response(&state, user_id, room_id, query, limit).await,
+        ClientReq::SaveDraft { room_id, content } => {
+            save_draft_response(&state, user_id, room_id, content).await
+        }
+        ClientReq::GetDraft { room_id } => get_draft_response(&state, user_id, room_id).await,
+        ClientReq::ConnectionInfo => {
+            connection_info_response(state, user_id, connection_id, connected_since, exp)
+        }
+        // Rejected by parse_client_req before dispatch
+        ClientReq::Unknown => {}
     }
 }
+
+/// Diagnostics about this socket, which only the router knows about
+fn connection_info_response(
+    state: &AppState,
+    user_id: Uuid,
+    connection_id: Uuid,
+    connected_since: DateTime<Utc>,
+    exp: usize,
+) {
+    let _ = send_event_to_connection(
+        state,
+        user_id,
+        connection_id,
+        ServerResp::ConnectionInfo {
+            connection_id,
+            connected_since,
+            token_expires_at: DateTime::from_timestamp(exp as i64, 0).unwrap_or_default(),
+            server_version: env!("CARGO_PKG_VERSION").to_string(),
+        },
+    );
+}
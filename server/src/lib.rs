@@ -15,3 +15,11 @@ pub fn create_app(state: AppState) -> Router {
         .layer(CorsLayer::permissive())
         .layer(TraceLayer::new_for_http())
 }
+
+/// For `axum::serve(..).with_graceful_shutdown`: once `signal` fires, every
+/// WebSocket client is told the server is going away and its socket closed,
+/// since upgraded connections would otherwise just be dropped
+pub async fn graceful_shutdown(state: AppState, signal: impl Future<Output = ()>) {
+    signal.await;
+    handler::close_connections_for_shutdown(&state);
+}
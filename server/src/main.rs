@@ -1,14 +1,26 @@
 use dashmap::DashMap;
 use dotenvy::dotenv;
 use server::config::{AppState, Config};
-use server::create_app;
 use server::database::db::Db;
+use server::database::invitations::InvitationRepository;
+use server::database::refresh_token::RefreshTokenRepository;
+use server::utils::metrics::Metrics;
+use server::utils::notifier::{NoopNotifier, Notifier, WebhookNotifier};
+use server::utils::tasks::BackgroundTasks;
+use server::{create_app, graceful_shutdown};
 use sqlx::postgres::PgPoolOptions;
-use std::sync::Arc;
+use std::{
+    net::SocketAddr,
+    sync::{Arc, atomic::AtomicUsize},
+    time::Duration,
+};
 use tokio::net::TcpListener;
-use tracing::info;
+use tracing::{error, info};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+const INVITATION_CLEANUP_INTERVAL_SECS: u64 = 300;
+const REFRESH_TOKEN_CLEANUP_INTERVAL_SECS: u64 = 3600;
+
 #[tokio::main]
 async fn main() {
     dotenv().ok();
@@ -21,7 +33,13 @@ async fn main() {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    let config = Config::init();
+    let config = match Config::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Invalid configuration:\n{}", e);
+            std::process::exit(1);
+        }
+    };
 
     let pool = PgPoolOptions::new()
         .max_connections(5)
@@ -35,18 +53,94 @@ async fn main() {
         .expect("Failed to run migrations");
 
     let db = Db::new(pool);
+    let tasks = BackgroundTasks::new();
+
+    let cleanup_db = db.clone();
+    tasks.spawn_periodic(
+        "invitation_cleanup",
+        Duration::from_secs(INVITATION_CLEANUP_INTERVAL_SECS),
+        move || {
+            let db = cleanup_db.clone();
+            async move {
+                match db.cleanup_expired_invitations().await {
+                    Ok(0) => {}
+                    Ok(count) => info!("Removed {} expired invitations", count),
+                    Err(e) => error!("Failed to clean up expired invitations: {:?}", e),
+                }
+            }
+        },
+    );
+
+    let cleanup_db = db.clone();
+    let refresh_retention = chrono::Duration::seconds(config.refresh_expiry);
+    tasks.spawn_periodic(
+        "refresh_token_cleanup",
+        Duration::from_secs(REFRESH_TOKEN_CLEANUP_INTERVAL_SECS),
+        move || {
+            let db = cleanup_db.clone();
+            async move {
+                match db.purge_stale_refresh_tokens(refresh_retention).await {
+                    Ok(0) => {}
+                    Ok(count) => info!("Removed {} stale refresh tokens", count),
+                    Err(e) => error!("Failed to clean up refresh tokens: {:?}", e),
+                }
+            }
+        },
+    );
+
+    let notifier: Arc<dyn Notifier> = match &config.notifier_webhook_url {
+        Some(url) => Arc::new(WebhookNotifier::new(url.clone())),
+        None => Arc::new(NoopNotifier),
+    };
 
     let app_state = AppState {
         config: Arc::new(config.clone()),
         db,
         channels: Arc::new(DashMap::new()),
+        open_connections: Arc::new(AtomicUsize::new(0)),
+        notifier,
+        metrics: Arc::new(Metrics::default()),
+        typing: Arc::new(DashMap::new()),
     };
 
+    let shutdown_state = app_state.clone();
     let app = create_app(app_state);
 
-    let addr = format!("0.0.0.0:{}", config.port);
+    let addr = SocketAddr::new(config.bind_addr, config.port);
     info!("Listening on {}", addr);
 
     let listener = TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(graceful_shutdown(shutdown_state, shutdown_signal()))
+        .await
+        .unwrap();
+
+    info!("Stopping background tasks");
+    tasks.shutdown().await;
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl-C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    info!("Shutdown signal received");
 }
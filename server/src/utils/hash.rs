@@ -1,5 +1,5 @@
 use scrypt::{
-    Scrypt,
+    Params, Scrypt,
     password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString, rand_core::OsRng},
 };
 use sha2::{Digest, Sha256};
@@ -8,13 +8,13 @@ use tracing::{error, instrument};
 use crate::errors::error::AppError;
 
 #[instrument(skip(password))]
-pub fn hash_password(password: impl Into<String>) -> Result<String, AppError> {
+pub fn hash_password(password: impl Into<String>, params: Params) -> Result<String, AppError> {
     let password = password.into();
 
     let salt = SaltString::generate(&mut OsRng);
 
     let hashed_password = Scrypt
-        .hash_password(password.as_bytes(), &salt)
+        .hash_password_customized(password.as_bytes(), None, None, params, &salt)
         .map_err(|e| {
             error!("Failed to hash password: {:?}", e);
             AppError::Internal
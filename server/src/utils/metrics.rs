@@ -0,0 +1,75 @@
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use dashmap::DashMap;
+
+#[derive(Default)]
+struct EventStats {
+    count: AtomicU64,
+    total_micros: AtomicU64,
+}
+
+/// In-process counters rendered in the Prometheus text format
+#[derive(Default)]
+pub struct Metrics {
+    events: DashMap<&'static str, EventStats>,
+}
+
+impl Metrics {
+    pub fn record_event(&self, event_type: &'static str, elapsed: Duration) {
+        let stats = self.events.entry(event_type).or_default();
+        stats.count.fetch_add(1, Ordering::Relaxed);
+        stats
+            .total_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn event_count(&self, event_type: &str) -> u64 {
+        self.events
+            .get(event_type)
+            .map(|s| s.count.load(Ordering::Relaxed))
+            .unwrap_or(0)
+    }
+
+    pub fn render(&self) -> String {
+        let mut events: Vec<(&'static str, u64, u64)> = self
+            .events
+            .iter()
+            .map(|e| {
+                (
+                    *e.key(),
+                    e.count.load(Ordering::Relaxed),
+                    e.total_micros.load(Ordering::Relaxed),
+                )
+            })
+            .collect();
+        events.sort_by_key(|(event_type, _, _)| *event_type);
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "# HELP ws_events_total WebSocket requests handled, by type"
+        );
+        let _ = writeln!(out, "# TYPE ws_events_total counter");
+        for (event_type, count, _) in &events {
+            let _ = writeln!(out, "ws_events_total{{type=\"{}\"}} {}", event_type, count);
+        }
+        let _ = writeln!(
+            out,
+            "# HELP ws_event_duration_seconds_total Time spent handling WebSocket requests, by type"
+        );
+        let _ = writeln!(out, "# TYPE ws_event_duration_seconds_total counter");
+        for (event_type, _, micros) in &events {
+            let _ = writeln!(
+                out,
+                "ws_event_duration_seconds_total{{type=\"{}\"}} {:.6}",
+                event_type,
+                *micros as f64 / 1_000_000.0
+            );
+        }
+        out
+    }
+}
@@ -18,6 +18,18 @@ pub struct AuthUser {
     pub role: UserRole,
 }
 
+impl AuthUser {
+    pub fn require_admin(&self) -> Result<(), AppError> {
+        match self.role {
+            UserRole::Admin => Ok(()),
+            _ => {
+                warn!("User {} is not an admin", self.user_id);
+                Err(AppError::NotAdmin)
+            }
+        }
+    }
+}
+
 impl FromRequestParts<AppState> for AuthUser {
     type Rejection = AppError;
 
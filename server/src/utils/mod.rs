@@ -1,4 +1,9 @@
+pub mod cursor;
 pub mod hash;
+pub mod metrics;
 pub mod middleware;
+pub mod notifier;
+pub mod rate_limit;
+pub mod tasks;
 pub mod token;
 pub mod validation;
@@ -0,0 +1,125 @@
+use async_trait::async_trait;
+use serde_json::json;
+use tracing::{error, instrument, warn};
+use uuid::Uuid;
+
+use crate::errors::error::AppError;
+
+/// Delivers out-of-band messages (e.g. password reset tokens) to users.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn send_password_reset(
+        &self,
+        user_id: Uuid,
+        email: &str,
+        token: &str,
+    ) -> Result<(), AppError>;
+    async fn send_recovery_email_verification(
+        &self,
+        user_id: Uuid,
+        email: &str,
+        token: &str,
+    ) -> Result<(), AppError>;
+}
+
+/// Posts notifications as JSON to a configured webhook, which is responsible
+/// for the actual email delivery.
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+        }
+    }
+
+    async fn post(
+        &self,
+        kind: &str,
+        user_id: Uuid,
+        email: &str,
+        token: &str,
+    ) -> Result<(), AppError> {
+        self.client
+            .post(&self.url)
+            .json(&json!({
+                "kind": kind,
+                "email": email,
+                "token": token,
+            }))
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|e| {
+                error!(
+                    "Failed to deliver {} notification for user {}: {:?}",
+                    kind, user_id, e
+                );
+                AppError::Internal
+            })?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    #[instrument(skip(self, email, token))]
+    async fn send_password_reset(
+        &self,
+        user_id: Uuid,
+        email: &str,
+        token: &str,
+    ) -> Result<(), AppError> {
+        self.post("password_reset", user_id, email, token).await
+    }
+
+    #[instrument(skip(self, email, token))]
+    async fn send_recovery_email_verification(
+        &self,
+        user_id: Uuid,
+        email: &str,
+        token: &str,
+    ) -> Result<(), AppError> {
+        self.post("recovery_email_verification", user_id, email, token)
+            .await
+    }
+}
+
+/// Used when no webhook is configured. Nothing is delivered.
+pub struct NoopNotifier;
+
+#[async_trait]
+impl Notifier for NoopNotifier {
+    #[instrument(skip(self, _email, _token))]
+    async fn send_password_reset(
+        &self,
+        user_id: Uuid,
+        _email: &str,
+        _token: &str,
+    ) -> Result<(), AppError> {
+        warn!(
+            "No notifier configured, dropping password reset for user {}",
+            user_id
+        );
+        Ok(())
+    }
+
+    #[instrument(skip(self, _email, _token))]
+    async fn send_recovery_email_verification(
+        &self,
+        user_id: Uuid,
+        _email: &str,
+        _token: &str,
+    ) -> Result<(), AppError> {
+        warn!(
+            "No notifier configured, dropping recovery email verification for user {}",
+            user_id
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+use std::time::{Duration, Instant};
+
+/// Allows `capacity` requests per `window`, refilling continuously rather than
+/// all at once when the window ends
+#[derive(Debug)]
+pub struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Starts full, so a fresh connection can burst up to `capacity`
+    pub fn new(capacity: u32, window: Duration) -> Self {
+        Self::new_at(capacity, window, Instant::now())
+    }
+
+    pub fn new_at(capacity: u32, window: Duration, now: Instant) -> Self {
+        let capacity = capacity as f64;
+        Self {
+            capacity,
+            tokens: capacity,
+            refill_per_sec: capacity / window.as_secs_f64(),
+            last_refill: now,
+        }
+    }
+
+    /// Takes a token if one is available
+    pub fn try_acquire(&mut self) -> bool {
+        self.try_acquire_at(Instant::now())
+    }
+
+    pub fn try_acquire_at(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
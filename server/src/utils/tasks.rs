@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use tracing::info;
+
+/// Background jobs sharing one cancellation token, so shutdown can stop
+/// all of them and wait for any run in progress to finish.
+#[derive(Clone, Default)]
+pub struct BackgroundTasks {
+    token: CancellationToken,
+    tracker: TaskTracker,
+}
+
+impl BackgroundTasks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Runs `job` every `period`, starting immediately, until shutdown.
+    pub fn spawn_periodic<F, Fut>(&self, name: &'static str, period: Duration, mut job: F)
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let token = self.token.clone();
+        self.tracker.spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                tokio::select! {
+                    _ = token.cancelled() => break,
+                    _ = interval.tick() => job().await,
+                }
+            }
+            info!("Background task {} stopped", name);
+        });
+    }
+
+    pub async fn shutdown(&self) {
+        self.tracker.close();
+        self.token.cancel();
+        self.tracker.wait().await;
+    }
+}
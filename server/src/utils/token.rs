@@ -46,9 +46,23 @@ pub fn generate_access_token(
 
 #[instrument]
 pub fn generate_refresh_token() -> Result<String, AppError> {
+    generate_opaque_token()
+}
+
+#[instrument]
+pub fn generate_password_reset_token() -> Result<String, AppError> {
+    generate_opaque_token()
+}
+
+#[instrument]
+pub fn generate_email_verification_token() -> Result<String, AppError> {
+    generate_opaque_token()
+}
+
+fn generate_opaque_token() -> Result<String, AppError> {
     let mut bytes = [0u8; 32];
     OsRng.fill_bytes(&mut bytes);
-    Ok(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(&bytes))
+    Ok(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes))
 }
 
 pub fn verify_access_token(
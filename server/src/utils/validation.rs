@@ -1,3 +1,6 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
 use serde_json::json;
 use tracing::{instrument, warn};
 
@@ -96,3 +99,72 @@ pub fn validate_confirm_password(password: &str, confirm: &str) -> Vec<ApiErrorI
 
     errs
 }
+
+static EMAIL_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[^@\s]+@[^@\s]+\.[^@\s]+$").unwrap());
+
+#[instrument(skip(email))]
+pub fn validate_recovery_email(email: &str) -> Vec<ApiErrorItem> {
+    let mut errs = vec![];
+
+    if email.len() > 254 || !EMAIL_REGEX.is_match(email) {
+        warn!("Recovery email is invalid");
+        errs.push(ApiErrorItem::new(
+            error_codes::RECOVERY_EMAIL_INVALID,
+            json!({"max": 254}),
+        ));
+    }
+
+    errs
+}
+
+#[instrument(skip(description))]
+pub fn validate_room_description(description: &str) -> Vec<ApiErrorItem> {
+    let mut errs = vec![];
+
+    if description.chars().count() > 500 {
+        warn!("Room description is too long");
+        errs.push(ApiErrorItem::new(
+            error_codes::ROOM_DESCRIPTION_TOO_LONG,
+            json!({"max": 500}),
+        ));
+    }
+
+    errs
+}
+
+/// `max_len` counts characters, not bytes
+#[instrument(skip(content))]
+pub fn validate_message_content(content: &str, max_len: usize) -> Vec<ApiErrorItem> {
+    let mut errs = vec![];
+
+    if content.is_empty() {
+        warn!("Message content is empty");
+        errs.push(ApiErrorItem::new(
+            error_codes::MESSAGE_CONTENT_REQUIRED,
+            None,
+        ));
+    }
+
+    if content.chars().count() > max_len {
+        warn!("Message content is too long");
+        errs.push(ApiErrorItem::new(
+            error_codes::MESSAGE_TOO_LONG,
+            json!({"max": max_len}),
+        ));
+    }
+
+    errs
+}
+
+/// Drops surrounding whitespace only. Whitespace inside a message is the
+/// author's formatting, e.g. indentation in a code block, so it's kept.
+pub fn normalize_message_content(content: &str) -> String {
+    content.trim().to_string()
+}
+
+/// Room names are a single line, so runs of whitespace inside them collapse
+/// to one space as well
+pub fn normalize_room_name(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
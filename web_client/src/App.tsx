@@ -343,7 +343,8 @@ function App() {
                 type: 'send_message',
                 room_id: currentRoom,
                 content: fileMsgContent,
-                message_type: 'text' // Treat validation metadata message as text for encryption
+                message_type: 'text', // Treat validation metadata message as text for encryption
+                file_id: res.data.file_id, // Keeps the upload from being cleaned up as an orphan
             });
 
             setNotification({
@@ -65,7 +65,7 @@ export type ClientReq =
     | { type: 'invite'; room_id: string; username: string }
     | { type: 'decline_invitation'; invitation_id: string }
     | { type: 'get_pending_invitations' }
-    | { type: 'send_message'; room_id: string; content: string; message_type?: 'text' | 'file' | 'system' }
+    | { type: 'send_message'; room_id: string; content: string; message_type?: 'text' | 'file' | 'system'; file_id?: string }
     | { type: 'edit_message'; message_id: string; new_content: string }
     | { type: 'delete_message'; message_id: string }
     | { type: 'get_messages'; room_id: string; limit: number; offset: number }